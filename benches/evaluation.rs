@@ -0,0 +1,98 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rxl::{parser::Parser, table::Table, tokenizer::Tokenizer};
+
+/// `sum(a1:b1) * (c1 - 1.5) + sum(a1:b2) * (c2 - 2.5) + ...` with `terms` calls.
+fn large_formula(terms: usize) -> String {
+    let calls = (1..=terms)
+        .map(|i| format!("sum(a1:b{i}) * (c{i} - {i}.5)"))
+        .collect::<Vec<_>>();
+    calls.join(" + ")
+}
+
+/// A single column where every cell adds one to the cell above it.
+fn dependency_chain(rows: usize) -> String {
+    let mut lines = vec!["1".to_string()];
+    lines.extend((1..rows).map(|row| format!("=a{row}+1")));
+    lines.join("\n")
+}
+
+/// `cols` columns of numbers with a final row summing each column.
+fn wide_sheet(rows: usize, cols: usize) -> String {
+    let mut lines = (0..rows)
+        .map(|row| {
+            (0..cols)
+                .map(|col| (row * cols + col).to_string())
+                .collect::<Vec<_>>()
+                .join("|")
+        })
+        .collect::<Vec<_>>();
+    let totals = (0..cols)
+        .map(|col| {
+            let name = rxl::tokenizer::cell_name(0, col);
+            let column = name.trim_end_matches('1');
+            format!("=sum({column}1:{column}{rows})")
+        })
+        .collect::<Vec<_>>();
+    lines.push(totals.join("|"));
+    lines.join("\n")
+}
+
+fn tokenize(c: &mut Criterion) {
+    let source = large_formula(200).chars().collect::<Vec<_>>();
+    c.bench_function("tokenize large formula", |b| {
+        b.iter(|| Tokenizer::new(black_box(&source)).count())
+    });
+}
+
+fn parse(c: &mut Criterion) {
+    let source = large_formula(200).chars().collect::<Vec<_>>();
+    c.bench_function("parse large formula", |b| {
+        b.iter(|| {
+            let mut tokenizer = Tokenizer::new(black_box(&source));
+            Parser::new(&mut tokenizer).ast().unwrap()
+        })
+    });
+}
+
+fn evaluate_chain(c: &mut Criterion) {
+    let source = dependency_chain(300);
+    c.bench_function("evaluate dependency chain", |b| {
+        b.iter(|| {
+            let mut table = Table::new_interpet(black_box(&source)).unwrap();
+            table.run();
+            table
+        })
+    });
+}
+
+fn evaluate_wide_sums(c: &mut Criterion) {
+    let source = wide_sheet(500, 20);
+    c.bench_function("evaluate wide sum ranges", |b| {
+        b.iter(|| {
+            let mut table = Table::new_interpet(black_box(&source)).unwrap();
+            table.run();
+            table
+        })
+    });
+}
+
+fn construct_large_sheet(c: &mut Criterion) {
+    let source = wide_sheet(2000, 20);
+    c.bench_function("construct large sheet", |b| {
+        b.iter(|| Table::new_interpet(black_box(&source)).unwrap())
+    });
+    #[cfg(feature = "parallel")]
+    c.bench_function("construct large sheet in parallel", |b| {
+        b.iter(|| Table::new_interpet_parallel(black_box(&source)).unwrap())
+    });
+}
+
+criterion_group!(
+    benches,
+    tokenize,
+    parse,
+    evaluate_chain,
+    evaluate_wide_sums,
+    construct_large_sheet
+);
+criterion_main!(benches);
@@ -1,9 +1,17 @@
-use bigdecimal::BigDecimal;
+use alloc::{boxed::Box, collections::BTreeSet, format, string::String, vec, vec::Vec};
+#[cfg(not(feature = "fast-float"))]
+use bigdecimal::Zero;
+use bigdecimal::{RoundingMode, ToPrimitive};
+use core::{cmp::Ordering, str::FromStr};
 
 use crate::{
+    date::Date,
     error::{TableError, TableResult},
-    eval::Evaluate,
-    tokenizer::Token,
+    eval::{bigdecimal_to_index, EvalContext, Evaluate},
+    format::format_number,
+    number::{ensure_finite, Number},
+    tokenizer::{cell_name, Token},
+    value::Value,
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -21,7 +29,7 @@ pub enum Expr {
     },
     Call {
         calle: Box<Expr>,
-        arguments: Vec<Box<Expr>>,
+        arguments: Vec<Expr>,
     },
 }
 
@@ -52,18 +60,648 @@ impl Expr {
     pub fn call(calle: Expr, arguments: Vec<Expr>) -> Self {
         Self::Call {
             calle: Box::new(calle),
-            arguments: arguments
-                .into_iter()
-                .map(|e| Box::new(e))
-                .collect::<Vec<_>>(),
+            arguments,
+        }
+    }
+
+    /// Evaluates the expression like `evaluate`, additionally appending a line
+    /// `expression = value` for every sub-expression to `steps`, indented by depth.
+    /// Constant literals and groupings are not recorded on their own.
+    pub fn explain<P>(
+        &self,
+        context: &EvalContext,
+        get_cell_value: &mut P,
+        depth: usize,
+        steps: &mut Vec<String>,
+    ) -> Vec<TableResult<Value>>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        use Expr::*;
+        let children = match self {
+            Grouping(expr) => return expr.explain(context, get_cell_value, depth, steps),
+            Literal(Token::Number(_) | Token::Bool(_) | Token::Str(_)) => {
+                return self.evaluate(context, get_cell_value)
+            }
+            Binary { left, right, .. } => vec![left.as_ref(), right.as_ref()],
+            Unary { right, .. } => vec![right.as_ref()],
+            Call { arguments, .. } => arguments.iter().collect(),
+            Literal(_) => Vec::new(),
+        };
+
+        let index = steps.len();
+        steps.push(String::new());
+        for child in children {
+            child.explain(context, get_cell_value, depth + 1, steps);
+        }
+        let res = self.evaluate(context, get_cell_value);
+        let value = match res.as_slice() {
+            [Ok(d)] => format!("{d}"),
+            [Err(e)] => format!("{e}"),
+            values => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Ok(d) => format!("{d}"),
+                        Err(e) => format!("{e}"),
+                    })
+                    .collect::<Vec<_>>();
+                format!("[{}]", values.join(", "))
+            }
+        };
+        steps[index] = format!("{}{self} = {value}", "  ".repeat(depth));
+        res
+    }
+
+    /// `min`/`max`: the extreme of every value of every argument. Empty cells are
+    /// skipped, and having no values at all is an error rather than 0.
+    fn extreme<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+        keep: Ordering,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let mut extreme: Option<Number> = None;
+        for arg in arguments {
+            for value in arg.evaluate(context, get_cell_value) {
+                let value = match value {
+                    Ok(Value::Empty) => continue,
+                    Ok(value) => self.number(context, &value)?,
+                    Err(e) => return Err(e),
+                };
+                extreme = match extreme {
+                    Some(e) if e.cmp(&value) == keep => Some(e),
+                    _ => Some(value),
+                };
+            }
+        }
+        extreme.ok_or_else(|| TableError::runtime_error(format!("No values in `{self}`")))
+    }
+
+    /// `count(range, ...)`: how many values are numbers. Empty cells and other
+    /// values are not counted; cells that fail to evaluate are an error.
+    fn count<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let mut count = 0u64;
+        for arg in arguments {
+            for value in arg.evaluate(context, get_cell_value) {
+                match value {
+                    Ok(Value::Number(_)) => count += 1,
+                    Ok(_) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(Number::from(count))
+    }
+
+    /// `maxifs`/`minifs`: the extreme of `return_range` over the positions where
+    /// `criteria_range` equals `criterion`. When nothing matches the result is 0,
+    /// as in other spreadsheets; criteria cells that fail to evaluate never match.
+    fn extreme_if<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+        keep: Ordering,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [return_range, criteria_range, criterion] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (return_range, criteria_range, criterion) in `{self}`"
+            )));
+        };
+        let values = return_range.evaluate(context, get_cell_value);
+        let criteria = criteria_range.evaluate(context, get_cell_value);
+        if values.len() != criteria.len() {
+            return Err(TableError::runtime_error(format!(
+                "Ranges have different sizes in `{self}`"
+            )));
+        }
+        let criterion = match criterion.evaluate(context, get_cell_value).as_slice() {
+            [c] => c.clone()?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+
+        let mut extreme: Option<Number> = None;
+        for (value, criteria) in values.into_iter().zip(criteria) {
+            if criteria.ok() != Some(criterion.clone()) {
+                continue;
+            }
+            let value = self.number(context, &value?)?;
+            extreme = match extreme {
+                Some(e) if e.cmp(&value) == keep => Some(e),
+                _ => Some(value),
+            };
+        }
+        Ok(extreme.unwrap_or_else(|| Number::from(0)))
+    }
+
+    /// The option `choose(index, option, ...)` selects by its 1-based index. Only
+    /// the index is evaluated, so errors in the other options do not matter.
+    fn choose<'a, P>(
+        &self,
+        context: &EvalContext,
+        arguments: &'a [Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<&'a Expr>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [index, options @ ..] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (index, option, ...) in `{self}`"
+            )));
+        };
+        let index = match index.evaluate(context, get_cell_value).as_slice() {
+            [index] => bigdecimal_to_index(&self.number(context, &index.clone()?)?)?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        match index.checked_sub(1).and_then(|i| options.get(i)) {
+            Some(option) => Ok(option),
+            None => Err(TableError::runtime_error(format!(
+                "Choice {index} is out of range for {} options in `{self}`",
+                options.len()
+            ))),
+        }
+    }
+
+    /// The branch `if(condition, then, else)` takes. A nonzero number or `true`
+    /// is true; only the condition is evaluated, as in `choose`.
+    fn branch<'a, P>(
+        &self,
+        context: &EvalContext,
+        arguments: &'a [Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<&'a Expr>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [condition, then, otherwise] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (condition, then, else) in `{self}`"
+            )));
+        };
+        let condition = match condition.evaluate(context, get_cell_value).as_slice() {
+            [condition] => self.number(context, &condition.clone()?)?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        Ok(if condition.is_zero() { otherwise } else { then })
+    }
+
+    /// `large`/`small`: the `n`th (1-based) largest or smallest number in `range`.
+    fn nth_value<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+        largest: bool,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [range, n] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (range, n) in `{self}`"
+            )));
+        };
+        let mut values = range
+            .evaluate(context, get_cell_value)
+            .into_iter()
+            .map(|value| self.number(context, &value?))
+            .collect::<TableResult<Vec<_>>>()?;
+        let n = match n.evaluate(context, get_cell_value).as_slice() {
+            [n] => bigdecimal_to_index(&self.number(context, &n.clone()?)?)?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        if n == 0 || n > values.len() {
+            return Err(TableError::runtime_error(format!(
+                "Position {n} is out of range for {} values in `{self}`",
+                values.len()
+            )));
+        }
+
+        values.sort();
+        let index = if largest { values.len() - n } else { n - 1 };
+        Ok(values.swap_remove(index))
+    }
+
+    /// `round(value, digits)` using the context's rounding mode. `digits` must
+    /// be a whole number of zero or more.
+    fn round<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [value, digits] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (value, digits) in `{self}`"
+            )));
+        };
+        let mut scalar = |expr: &Expr| match expr.evaluate(context, get_cell_value).as_slice() {
+            [v] => self.number(context, &v.clone()?),
+            _ => Err(TableError::MultipleCellReturn),
+        };
+        let value = scalar(value)?;
+        let digits = scalar(digits)?;
+        let digits = match digits.is_integer() {
+            true => digits.to_i64().filter(|digits| *digits >= 0),
+            false => None,
+        }
+        .ok_or_else(|| {
+            TableError::runtime_error(format!("Expected whole number of digits in `{self}`"))
+        })?;
+        Ok(context.clamp(value.with_scale_round(digits, context.rounding)))
+    }
+
+    /// `abs(value)`: the absolute value of a single number.
+    fn abs<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [value] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (value) in `{self}`"
+            )));
+        };
+        match value.evaluate(context, get_cell_value).as_slice() {
+            [value] => Ok(self.number(context, &value.clone()?)?.abs()),
+            _ => Err(TableError::runtime_error(format!(
+                "Expected a single value in `{self}`"
+            ))),
+        }
+    }
+
+    /// `sumproduct(range, ...)`: the sum of the products of the ranges'
+    /// corresponding values. The ranges must all be the same size.
+    fn sum_product<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [first, rest @ ..] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (range, ...) in `{self}`"
+            )));
+        };
+        let mut products = first
+            .evaluate(context, get_cell_value)
+            .into_iter()
+            .map(|value| self.number(context, &value?))
+            .collect::<TableResult<Vec<_>>>()?;
+        for range in rest {
+            let values = range.evaluate(context, get_cell_value);
+            if values.len() != products.len() {
+                return Err(TableError::runtime_error(format!(
+                    "Ranges have different sizes in `{self}`"
+                )));
+            }
+            for (product, value) in products.iter_mut().zip(values) {
+                *product =
+                    context.limit_precision(product.clone() * self.number(context, &value?)?)?;
+            }
+        }
+        let mut total = Number::from(0);
+        for product in products {
+            total = context.clamp(total + product);
+        }
+        Ok(total)
+    }
+
+    /// `year`/`month`/`day`/`weekday(date)`: a component of a date written as
+    /// `YYYY-MM-DD` text. `weekday` counts from 1 for Sunday to 7 for Saturday.
+    fn date_part<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+        part: &Token,
+    ) -> TableResult<Number>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [date] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (date) in `{self}`"
+            )));
+        };
+        let date = match date.evaluate(context, get_cell_value).as_slice() {
+            [Ok(Value::Text(date))] => Date::parse_iso(date)?,
+            [Err(e)] => Err(e.clone())?,
+            [_] => Err(TableError::runtime_error(format!(
+                "Expected a date in `{self}`"
+            )))?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        Ok(match part {
+            Token::Year => Number::from(date.year),
+            Token::Month => Number::from(date.month),
+            Token::Day => Number::from(date.day),
+            _ => Number::from(date.weekday()),
+        })
+    }
+
+    /// `prev(cell)`: the cell's value as of the previous `Table::run_step`, or 0
+    /// before the first step. The cell is not evaluated.
+    fn prev(&self, context: &EvalContext, arguments: &[Expr]) -> TableResult<Value> {
+        let [Expr::Literal(Token::CellRef(cell))] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (cell) in `{self}`"
+            )));
+        };
+        Ok(context
+            .previous
+            .get(&cell.coord())
+            .cloned()
+            .unwrap_or_else(|| Value::Number(Number::from(0))))
+    }
+
+    /// `type(value)`: the kind of the value, one of "number", "text", "bool",
+    /// "empty" for an empty cell and "error" when it fails to evaluate.
+    fn value_type<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<String>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [value] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (value) in `{self}`"
+            )));
+        };
+        let kind = match value.evaluate(context, get_cell_value).as_slice() {
+            [Ok(Value::Number(_))] => "number",
+            [Ok(Value::Text(_))] => "text",
+            [Ok(Value::Bool(_))] => "bool",
+            [Ok(Value::Empty)] => "empty",
+            [Err(_)] => "error",
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        Ok(kind.into())
+    }
+
+    /// `text(value, pattern)`: formats a number with a pattern like `"#,##0.00"`.
+    fn text<P>(
+        &self,
+        context: &EvalContext,
+        arguments: &[Expr],
+        get_cell_value: &mut P,
+    ) -> TableResult<String>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        let [value, pattern] = arguments else {
+            return Err(TableError::runtime_error(format!(
+                "Expected (value, pattern) in `{self}`"
+            )));
+        };
+        let value = match value.evaluate(context, get_cell_value).as_slice() {
+            [v] => self.number(context, &v.clone()?)?,
+            _ => Err(TableError::MultipleCellReturn)?,
+        };
+        let pattern = match pattern.evaluate(context, get_cell_value).as_slice() {
+            [Ok(Value::Text(pattern))] => pattern.clone(),
+            [Err(e)] => Err(e.clone())?,
+            _ => Err(TableError::runtime_error(format!(
+                "Expected a text pattern in `{self}`"
+            )))?,
+        };
+        format_number(&value, &pattern, context.rounding)
+    }
+
+    /// Applies a binary `operator` to one pair of operand values.
+    fn binary_value(
+        &self,
+        context: &EvalContext,
+        operator: &Token,
+        left: TableResult<Value>,
+        right: TableResult<Value>,
+    ) -> TableResult<Value> {
+        let (left, right) = (left?, right?);
+        if *operator == Token::Ampersand {
+            return Ok(Value::Text(format!("{left}{right}")));
+        }
+        let (left, right) = (self.number(context, &left)?, self.number(context, &right)?);
+        if matches!(operator, Token::Slash | Token::SlashSlash) && right.is_zero() {
+            return Err(TableError::DivisionByZero);
+        }
+        let holds = match operator {
+            Token::Less => Some(left < right),
+            Token::LessEqual => Some(left <= right),
+            Token::Greater => Some(left > right),
+            Token::GreaterEqual => Some(left >= right),
+            Token::Equal => Some(left == right),
+            Token::NotEqual => Some(left != right),
+            _ => None,
+        };
+        if let Some(holds) = holds {
+            return Ok(Value::Number(Number::from(u8::from(holds))));
+        }
+        let res = match operator {
+            Token::Plus => left + right,
+            Token::Slash => context.round_quotient(left / right),
+            Token::SlashSlash => (left / right).with_scale_round(0, RoundingMode::Floor),
+            Token::Minus => left - right,
+            Token::Star => context.limit_precision(left * right)?,
+            Token::Caret => self.power(context, left, right)?,
+            _ => {
+                return Err(TableError::RuntimeError(format!(
+                    "invalid token in binary expression `{self}`"
+                )))
+            }
+        };
+        Ok(Value::Number(context.clamp(res)))
+    }
+
+    /// `base` raised to a whole `exponent` by repeated squaring, keeping every
+    /// product within the precision budget. Fractional exponents are an error.
+    fn power(&self, context: &EvalContext, base: Number, exponent: Number) -> TableResult<Number> {
+        let Some(n) = exponent.is_integer().then(|| exponent.to_i64()).flatten() else {
+            return Err(TableError::runtime_error(format!(
+                "Exponent {exponent} is not a whole number in `{self}`"
+            )));
+        };
+        if n < 0 && base.is_zero() {
+            return Err(TableError::DivisionByZero);
+        }
+        let mut result = Number::from(1);
+        let mut square = base;
+        let mut remaining = n.unsigned_abs();
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = context.limit_precision(result * square.clone())?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                square = context.limit_precision(square.clone() * square)?;
+            }
+        }
+        if n < 0 {
+            Ok(context.round_quotient(Number::from(1) / result))
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// An operand as a number, naming this expression when it holds text. Text
+    /// that reads as a number is converted if the context coerces text numbers.
+    fn number(&self, context: &EvalContext, value: &Value) -> TableResult<Number> {
+        match value {
+            Value::Text(s) => context
+                .coerce_text_numbers
+                .then(|| Number::from_str(s.trim()).ok())
+                .flatten()
+                .ok_or_else(|| {
+                    TableError::runtime_error(format!(
+                        "Expected a number in `{self}`, got text \"{s}\""
+                    ))
+                }),
+            value => value.as_number(),
+        }
+    }
+
+    /// Moves every cell reference with `shift`. A range moves its corners, so it
+    /// grows when only its far corner moves.
+    pub fn shift_references<F>(&mut self, shift: &F)
+    where
+        F: Fn((usize, usize)) -> (usize, usize),
+    {
+        use Expr::*;
+        match self {
+            Binary { left, right, .. } => {
+                left.shift_references(shift);
+                right.shift_references(shift);
+            }
+            Grouping(expr) => expr.shift_references(shift),
+            Literal(Token::CellRef(cell)) => (cell.row, cell.col) = shift(cell.coord()),
+            Literal(Token::CellRange(range)) => {
+                for corner in [&mut range.start, &mut range.end] {
+                    (corner.row, corner.col) = shift(corner.coord());
+                }
+            }
+            Literal(_) => {}
+            Unary { right, .. } => right.shift_references(shift),
+            Call { calle, arguments } => {
+                calle.shift_references(shift);
+                for arg in arguments {
+                    arg.shift_references(shift);
+                }
+            }
+        }
+    }
+
+    fn collect_references(&self, references: &mut Vec<(usize, usize)>) {
+        use Expr::*;
+        match self {
+            Binary { left, right, .. } => {
+                left.collect_references(references);
+                right.collect_references(references);
+            }
+            Grouping(expr) => expr.collect_references(references),
+            Literal(Token::CellRef(cell)) => references.push(cell.coord()),
+            Literal(Token::CellRange(range)) => {
+                for col in range.cols() {
+                    for row in range.rows() {
+                        references.push((row, col));
+                    }
+                }
+            }
+            Literal(_) => {}
+            Unary { right, .. } => right.collect_references(references),
+            // `prev` reads the previous step's values, not the cells' current ones.
+            Call { calle, .. } if **calle == Literal(Token::Prev) => {}
+            Call { calle, arguments } => {
+                calle.collect_references(references);
+                for arg in arguments {
+                    arg.collect_references(references);
+                }
+            }
+        }
+    }
+
+    /// Whether the expression reads a `@label`, whose cell is only known from
+    /// the context it is evaluated in.
+    pub fn has_labels(&self) -> bool {
+        use Expr::*;
+        match self {
+            Binary { left, right, .. } => left.has_labels() || right.has_labels(),
+            Grouping(expr) => expr.has_labels(),
+            Literal(token) => token.is_label(),
+            Unary { right, .. } => right.has_labels(),
+            Call { calle, arguments } => {
+                calle.has_labels() || arguments.iter().any(Expr::has_labels)
+            }
         }
     }
 }
 
-impl Evaluate for Expr {
-    fn evaluate<P>(&self, get_cell_value: &mut P) -> Vec<TableResult<BigDecimal>>
+impl core::fmt::Display for Expr {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Expr::Binary {
+                left,
+                operator,
+                right,
+            } => write!(f, "{left} {operator} {right}"),
+            Expr::Grouping(expr) => write!(f, "({expr})"),
+            Expr::Literal(token) => write!(f, "{token}"),
+            Expr::Unary {
+                operator: Token::Percent,
+                right,
+            } => write!(f, "{right}%"),
+            Expr::Unary { operator, right } => write!(f, "{operator}{right}"),
+            Expr::Call { calle, arguments } => {
+                write!(f, "{calle}(")?;
+                for (i, arg) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression; `evaluate` then checks every number it gives.
+    fn evaluate_values<P>(
+        &self,
+        context: &EvalContext,
+        get_cell_value: &mut P,
+    ) -> Vec<TableResult<Value>>
     where
-        P: FnMut(usize, usize) -> TableResult<BigDecimal>,
+        P: FnMut(usize, usize) -> TableResult<Value>,
     {
         use Expr::*;
         use Token::*;
@@ -73,115 +711,470 @@ impl Evaluate for Expr {
                 operator,
                 right,
             } => {
-                let left = left.evaluate(get_cell_value);
-                let right = right.evaluate(get_cell_value);
-                if left.len() != 1 || right.len() != 1 {
-                    return vec![Err(TableError::runtime_error(format!(
-                        "Cannot {operator:?} cell ranges together"
-                    )))];
-                }
-
-                let left = left[0].clone();
-
-                let right = right[0].clone();
-
-                if let (Ok(left), Ok(right)) = (left, right) {
-                    let res = match operator {
-                        Plus => Ok(left + right),
-                        Slash => Ok(left / right),
-                        Minus => Ok(left - right),
-                        Star => Ok(left * right),
-                        _ => Err(TableError::RuntimeError(format!(
-                            "invalid token in binary expression: {operator:?}"
-                        ))),
-                    };
-                    vec![res]
-                } else {
-                    vec![Err(TableError::runtime_error(
-                        "Error performing binary operation on two cells",
-                    ))]
-                }
+                let left = left.evaluate(context, get_cell_value);
+                let right = right.evaluate(context, get_cell_value);
+                // A single value is broadcast across a range on the other side.
+                let pairs: Vec<_> = match (left.len(), right.len()) {
+                    (1, _) => right.into_iter().map(|r| (left[0].clone(), r)).collect(),
+                    (_, 1) => left.into_iter().map(|l| (l, right[0].clone())).collect(),
+                    _ => {
+                        return vec![Err(TableError::runtime_error(format!(
+                            "Cannot {operator:?} cell ranges together in `{self}`"
+                        )))]
+                    }
+                };
+                pairs
+                    .into_iter()
+                    .map(|(left, right)| self.binary_value(context, operator, left, right))
+                    .collect()
             }
-            Grouping(expr) => expr.evaluate(get_cell_value),
+            Grouping(expr) => expr.evaluate(context, get_cell_value),
             Literal(token) => match token {
-                Number(d) => vec![Ok(d.clone())],
-                CellRef((row, col)) => vec![get_cell_value(*row, *col)],
-                CellRange((row_range, col_range)) => {
+                Number(d) => vec![Ok(Value::Number(d.clone()))],
+                Bool(b) => vec![Ok(Value::Bool(*b))],
+                Str(s) => vec![Ok(Value::Text(s.clone()))],
+                Name(name) => match context.variables.get(name) {
+                    Some(d) => vec![Ok(Value::Number(d.clone()))],
+                    None => vec![Err(TableError::runtime_error(format!(
+                        "Unknown name `{name}`"
+                    )))],
+                },
+                Label(name) => match context.labels.get(name) {
+                    Some((row, col)) => vec![get_cell_value(*row, *col)],
+                    None => vec![Err(TableError::runtime_error(format!(
+                        "Unknown label `@{name}`"
+                    )))],
+                },
+                CellRef(cell) => {
+                    let variable = (!context.variables.is_empty())
+                        .then(|| cell_name(cell.row, cell.col))
+                        .and_then(|name| context.variables.get(&name));
+                    match variable {
+                        Some(d) => vec![Ok(Value::Number(d.clone()))],
+                        None => vec![get_cell_value(cell.row, cell.col)],
+                    }
+                }
+                CellRange(range) => {
+                    let (row_range, col_range) = (range.rows(), range.cols());
+                    let (row_range, col_range) = match context.range_bounds {
+                        Some((rows, cols)) => (
+                            row_range.start..row_range.end.min(rows),
+                            col_range.start..col_range.end.min(cols),
+                        ),
+                        None => (row_range, col_range),
+                    };
                     let mut cells = Vec::new();
-                    for col in col_range.clone().into_iter() {
-                        for row in row_range.clone().into_iter() {
+                    for col in col_range {
+                        for row in row_range.clone() {
                             cells.push(get_cell_value(row, col))
                         }
                     }
                     cells
                 }
                 _ => vec![Err(TableError::RuntimeError(format!(
-                    "invalid token literal {token:?}"
+                    "invalid token literal `{self}`"
                 )))],
             },
             Unary { operator, right } => {
-                let right = right.evaluate(get_cell_value);
+                let right = right.evaluate(context, get_cell_value);
                 if right.len() != 1 {
-                    return vec![Err(TableError::runtime_error(
-                        "Error in unary expression - expected single cell value",
-                    ))];
+                    return vec![Err(TableError::runtime_error(format!(
+                        "Error in unary expression `{self}` - expected single cell value"
+                    )))];
                 }
 
                 let right = right[0].clone();
 
                 match (operator, right) {
-                    (Minus, Ok(r)) => vec![Ok(-r)],
+                    (Minus, Ok(r)) => {
+                        vec![self
+                            .number(context, &r)
+                            .map(|r| Value::Number(context.clamp(-r)))]
+                    }
+                    (Percent, Ok(r)) => vec![self.number(context, &r).map(|r| {
+                        Value::Number(context.clamp(r / crate::number::Number::from(100)))
+                    })],
                     (_, Err(r)) => vec![Err(r)],
                     _ => vec![Err(TableError::RuntimeError(format!(
-                        "invalid token for unary expression {operator:?}"
+                        "invalid token for unary expression `{self}`"
                     )))],
                 }
             }
             Call { calle, arguments } => match *calle.clone() {
                 Expr::Literal(t) => match t {
+                    // sum, product and mean skip empty cells, like min, max and count.
                     Sum => {
-                        let mut counter = BigDecimal::from(0);
+                        let mut counter = crate::number::Number::from(0);
                         for arg in arguments {
-                            let res = arg.evaluate(get_cell_value);
+                            let res = arg.evaluate(context, get_cell_value);
                             for r in res {
-                                if let Ok(res) = r.clone() {
-                                    counter += res;
-                                } else {
-                                    return vec![Err(TableError::runtime_error(
-                                        "Error performing summation",
-                                    ))];
+                                if let Ok(Value::Empty) = r {
+                                    continue;
+                                }
+                                match r.and_then(|res| self.number(context, &res)) {
+                                    Ok(res) => counter = context.clamp(counter + res),
+                                    Err(e) => return vec![Err(e)],
                                 }
                             }
                         }
 
-                        vec![Ok(counter)]
+                        vec![Ok(Value::Number(counter))]
+                    }
+                    Product => {
+                        if arguments.is_empty() {
+                            return vec![Err(TableError::runtime_error(format!(
+                                "Expected at least one argument in `{self}`"
+                            )))];
+                        }
+                        let mut product = crate::number::Number::from(1);
+                        for arg in arguments {
+                            let res = arg.evaluate(context, get_cell_value);
+                            for r in res {
+                                if let Ok(Value::Empty) = r {
+                                    continue;
+                                }
+                                let res = r
+                                    .and_then(|res| self.number(context, &res))
+                                    .and_then(|res| context.limit_precision(product.clone() * res));
+                                match res {
+                                    Ok(res) => product = context.clamp(res),
+                                    Err(e) => return vec![Err(e)],
+                                }
+                            }
+                        }
+
+                        vec![Ok(Value::Number(product))]
                     }
                     Mean => {
-                        let mut counter = BigDecimal::from(0);
+                        let mut counter = crate::number::Number::from(0);
                         let mut n = 0;
                         for arg in arguments {
-                            let res = arg.evaluate(get_cell_value);
+                            let res = arg.evaluate(context, get_cell_value);
                             for r in res {
-                                if let Ok(res) = r.clone() {
-                                    counter += res;
-                                } else {
-                                    return vec![Err(TableError::runtime_error(
-                                        "Error performing summation",
-                                    ))];
+                                if let Ok(Value::Empty) = r {
+                                    continue;
+                                }
+                                match r.and_then(|res| self.number(context, &res)) {
+                                    Ok(res) => counter = context.clamp(counter + res),
+                                    Err(e) => return vec![Err(e)],
                                 }
+                                n += 1;
                             }
-                            n += 1;
                         }
-                        return vec![Ok(counter / n)];
+                        if n == 0 {
+                            return vec![Err(TableError::DivisionByZero)];
+                        }
+                        vec![Ok(Value::Number(
+                            context.clamp(counter / crate::number::Number::from(n)),
+                        ))]
                     }
+                    Count => vec![self
+                        .count(context, arguments, get_cell_value)
+                        .map(Value::Number)],
+                    Min => vec![self
+                        .extreme(context, arguments, get_cell_value, Ordering::Less)
+                        .map(Value::Number)],
+                    Max => vec![self
+                        .extreme(context, arguments, get_cell_value, Ordering::Greater)
+                        .map(Value::Number)],
+                    MaxIfs => {
+                        vec![self
+                            .extreme_if(context, arguments, get_cell_value, Ordering::Greater)
+                            .map(Value::Number)]
+                    }
+                    MinIfs => {
+                        vec![self
+                            .extreme_if(context, arguments, get_cell_value, Ordering::Less)
+                            .map(Value::Number)]
+                    }
+                    Large => vec![self
+                        .nth_value(context, arguments, get_cell_value, true)
+                        .map(Value::Number)],
+                    Small => vec![self
+                        .nth_value(context, arguments, get_cell_value, false)
+                        .map(Value::Number)],
+                    Abs => vec![self
+                        .abs(context, arguments, get_cell_value)
+                        .map(Value::Number)],
+                    Round => vec![self
+                        .round(context, arguments, get_cell_value)
+                        .map(Value::Number)],
+                    Text => vec![self
+                        .text(context, arguments, get_cell_value)
+                        .map(Value::Text)],
+                    Year | Month | Day | Weekday => vec![self
+                        .date_part(context, arguments, get_cell_value, &t)
+                        .map(Value::Number)],
+                    Prev => vec![self.prev(context, arguments)],
+                    Type => vec![self
+                        .value_type(context, arguments, get_cell_value)
+                        .map(Value::Text)],
+                    SumProduct => vec![self
+                        .sum_product(context, arguments, get_cell_value)
+                        .map(Value::Number)],
+                    If => match self.branch(context, arguments, get_cell_value) {
+                        Ok(branch) => branch.evaluate(context, get_cell_value),
+                        Err(e) => vec![Err(e)],
+                    },
+                    Choose => match self.choose(context, arguments, get_cell_value) {
+                        Ok(option) => option.evaluate(context, get_cell_value),
+                        Err(e) => vec![Err(e)],
+                    },
                     _ => vec![Err(TableError::RuntimeError(format!(
-                        "Invalid token encountered type for calle {t:?}"
+                        "Invalid token encountered type for calle in `{self}`"
                     )))],
                 },
                 _ => vec![Err(TableError::RuntimeError(format!(
-                    "Invalid expr type for calle {calle:?}"
+                    "Invalid expr type for calle in `{self}`"
                 )))],
             },
         }
     }
 }
+
+impl Evaluate for Expr {
+    fn references(&self) -> Vec<(usize, usize)> {
+        let mut references = Vec::new();
+        self.collect_references(&mut references);
+        let mut seen = BTreeSet::new();
+        references.retain(|coord| seen.insert(*coord));
+        references
+    }
+
+    fn evaluate<P>(&self, context: &EvalContext, get_cell_value: &mut P) -> Vec<TableResult<Value>>
+    where
+        P: FnMut(usize, usize) -> TableResult<Value>,
+    {
+        self.evaluate_values(context, get_cell_value)
+            .into_iter()
+            .map(|value| match value {
+                Ok(Value::Number(d)) => ensure_finite(d).map(Value::Number),
+                other => other,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamp() {
+        let context = EvalContext {
+            clamp: Some((Number::from(-10), Number::from(10))),
+            ..Default::default()
+        };
+        let sum = Expr::call(
+            Expr::literal(Token::Sum),
+            vec![Expr::literal(Token::CellRange((0..4, 0..1).into()))],
+        );
+        let res = sum.evaluate(&context, &mut |_, _| Ok(Value::Number(Number::from(4))));
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(10)));
+
+        let product = Expr::binary(
+            Expr::literal(Token::CellRef((0, 0).into())),
+            Token::Star,
+            Expr::literal(Token::Number(Number::from(-3))),
+        );
+        let res = product.evaluate(&context, &mut |_, _| Ok(Value::Number(Number::from(4))));
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(-10)));
+
+        let res = product.evaluate(&context, &mut |_, _| Ok(Value::Number(Number::from(2))));
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(-6)));
+    }
+
+    #[test]
+    fn test_count() {
+        let count = Expr::call(
+            Expr::literal(Token::Count),
+            vec![
+                Expr::literal(Token::CellRange((0..3, 0..2).into())),
+                Expr::literal(Token::Number(Number::from(7))),
+            ],
+        );
+        let mut cells = |row, col| match (row, col) {
+            (0, 0) | (2, 1) => Ok(Value::Number(Number::from(2))),
+            (1, 0) => Ok(Value::Text("1".into())),
+            (1, 1) => Ok(Value::Bool(true)),
+            _ => Ok(Value::Empty),
+        };
+        let res = count.evaluate(&EvalContext::default(), &mut cells);
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(3)));
+
+        let res = count.evaluate(&EvalContext::default(), &mut |_, _| Ok(Value::Empty));
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(1)));
+
+        let res = count.evaluate(&EvalContext::default(), &mut |row, _| match row {
+            0 => Err(TableError::RecursiveCellExpr(vec![(0, 0), (0, 0)])),
+            _ => Ok(Value::Number(Number::from(1))),
+        });
+        assert!(matches!(res[0], Err(TableError::RecursiveCellExpr(_))));
+    }
+
+    #[test]
+    fn test_product() {
+        let product = |arguments| Expr::call(Expr::literal(Token::Product), arguments);
+        let range = Expr::literal(Token::CellRange((0..5, 0..1).into()));
+        let mut ones_and_twos = |row, _| Ok(Value::Number(Number::from(1 + row as u32 % 2)));
+        let res =
+            product(vec![range.clone()]).evaluate(&EvalContext::default(), &mut ones_and_twos);
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(4)));
+
+        let res = product(vec![
+            range.clone(),
+            Expr::literal(Token::Number(Number::from(-3))),
+        ])
+        .evaluate(&EvalContext::default(), &mut ones_and_twos);
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(-12)));
+
+        let res = product(vec![range]).evaluate(&EvalContext::default(), &mut |row, _| match row {
+            3 => Ok(Value::Empty),
+            _ => Ok(Value::Number(Number::from(2))),
+        });
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(16)));
+
+        let res = product(vec![]).evaluate(&EvalContext::default(), &mut |_, _| unreachable!());
+        assert!(res[0].is_err());
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let zero = |_, _| Ok(Value::Number(Number::from(0)));
+        for operator in [Token::Slash, Token::SlashSlash] {
+            let quotient = Expr::binary(
+                Expr::literal(Token::Number(Number::from(1))),
+                operator,
+                Expr::literal(Token::CellRef((0, 1).into())),
+            );
+            let res = quotient.evaluate(&EvalContext::default(), &mut zero.clone());
+            assert!(matches!(res[0], Err(TableError::DivisionByZero)));
+        }
+
+        let reciprocal = Expr::binary(
+            Expr::literal(Token::CellRef((0, 0).into())),
+            Token::Caret,
+            Expr::literal(Token::Number(Number::from(-1))),
+        );
+        let res = reciprocal.evaluate(&EvalContext::default(), &mut zero.clone());
+        assert!(matches!(res[0], Err(TableError::DivisionByZero)));
+
+        let mean = Expr::call(Expr::literal(Token::Mean), vec![]);
+        let res = mean.evaluate(&EvalContext::default(), &mut |_, _| unreachable!());
+        assert!(matches!(res[0], Err(TableError::DivisionByZero)));
+
+        let sum = Expr::binary(
+            reciprocal,
+            Token::Plus,
+            Expr::literal(Token::Number(Number::from(1))),
+        );
+        let res = sum.evaluate(&EvalContext::default(), &mut zero.clone());
+        assert!(matches!(res[0], Err(TableError::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_mean_counts_values() {
+        let mean = Expr::call(
+            Expr::literal(Token::Mean),
+            vec![
+                Expr::literal(Token::CellRange((0..1, 0..3).into())),
+                Expr::literal(Token::Number(Number::from(6))),
+            ],
+        );
+        let res = mean.evaluate(&EvalContext::default(), &mut |_, col| {
+            Ok(Value::Number(Number::from(col as u32 + 1)))
+        });
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(3)));
+    }
+
+    #[test]
+    fn test_aggregates_skip_empty_cells() {
+        let range = || Expr::literal(Token::CellRange((0..4, 0..1).into()));
+        let mut threes = |row, _| match row {
+            1 => Ok(Value::Empty),
+            _ => Ok(Value::Number(Number::from(3 * row as u32))),
+        };
+        let expected = [
+            (Token::Sum, 15),
+            (Token::Mean, 5),
+            (Token::Min, 0),
+            (Token::Count, 3),
+        ];
+        for (function, value) in expected {
+            let res = Expr::call(Expr::literal(function), vec![range()])
+                .evaluate(&EvalContext::default(), &mut threes);
+            assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(value)));
+        }
+
+        let mean = Expr::call(Expr::literal(Token::Mean), vec![range()]);
+        let res = mean.evaluate(&EvalContext::default(), &mut |_, _| Ok(Value::Empty));
+        assert!(matches!(res[0], Err(TableError::DivisionByZero)));
+    }
+
+    #[test]
+    fn test_display() {
+        let expr = Expr::binary(
+            Expr::call(
+                Expr::literal(Token::Sum),
+                vec![
+                    Expr::literal(Token::CellRange((0..5, 0..2).into())),
+                    Expr::literal(Token::CellRef((0, 27).into())),
+                ],
+            ),
+            Token::Star,
+            Expr::grouping(Expr::unary(
+                Token::Minus,
+                Expr::literal(Token::Number(Number::from(3))),
+            )),
+        );
+        assert_eq!(alloc::format!("{expr}"), "sum(a1:b5, ab1) * (-3)");
+    }
+
+    #[test]
+    fn test_runtime_error_names_expression() {
+        let expr = Expr::binary(
+            Expr::literal(Token::CellRef((0, 0).into())),
+            Token::Plus,
+            Expr::literal(Token::CellRef((1, 1).into())),
+        );
+        let res = expr.evaluate(&EvalContext::default(), &mut |_, _| {
+            Ok(Value::Text("x".into()))
+        });
+        match &res[0] {
+            Err(TableError::RuntimeError(msg)) => assert!(msg.contains("`a1 + b2`")),
+            other => panic!("expected runtime error, got {other:?}"),
+        }
+
+        let res = expr.evaluate(&EvalContext::default(), &mut |_, _| Ok(Value::Empty));
+        assert!(matches!(res[0], Err(TableError::EmptyCellEvaluation)));
+    }
+
+    #[test]
+    fn test_block_range_iteration_order() {
+        let expr = Expr::call(
+            Expr::literal(Token::Sum),
+            vec![Expr::literal(Token::CellRange((0..3, 0..3).into()))],
+        );
+        let mut visited = Vec::new();
+        let res = expr.evaluate(&EvalContext::default(), &mut |row, col| {
+            visited.push((row, col));
+            Ok(Value::Number(Number::from((row * 3 + col + 1) as u32)))
+        });
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(45)));
+        assert_eq!(
+            visited,
+            vec![
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (0, 2),
+                (1, 2),
+                (2, 2)
+            ]
+        );
+    }
+}
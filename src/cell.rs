@@ -3,8 +3,11 @@ use super::error::*;
 use super::eval::Evaluate;
 use super::parser::Parser;
 use super::tokenizer::Tokenizer;
-use bigdecimal::BigDecimal;
-use std::str::FromStr;
+use super::value::Value;
+use crate::date::Date;
+use crate::number::Number;
+use alloc::{borrow::Cow, format, string::String, vec, vec::Vec};
+use core::str::FromStr;
 
 #[derive(Debug, Clone, Default)]
 pub enum CellKind<T: Evaluate + Clone> {
@@ -12,9 +15,18 @@ pub enum CellKind<T: Evaluate + Clone> {
     Empty,
     Expr {
         expr: T,
-        result: Option<TableResult<BigDecimal>>,
+        result: Option<TableResult<Value>>,
+    },
+    Number(Number),
+    Text(String),
+    Bool(bool),
+    /// A cell from `Table::new_interpet_lazy` whose `source` has not been parsed yet.
+    Unparsed,
+    /// A value written by a formula above this cell spilling its results.
+    Spilled {
+        origin: (usize, usize),
+        result: TableResult<Value>,
     },
-    Number(BigDecimal),
 }
 
 impl<T: Evaluate + Clone> CellKind<T> {
@@ -22,7 +34,7 @@ impl<T: Evaluate + Clone> CellKind<T> {
         Self::Expr { expr, result: None }
     }
 
-    fn new_number(d: BigDecimal) -> Self {
+    fn new_number(d: Number) -> Self {
         Self::Number(d)
     }
 }
@@ -32,32 +44,152 @@ pub struct Cell<'a, T>
 where
     T: Evaluate,
 {
-    pub source: &'a str,
+    pub source: Cow<'a, str>,
     pub kind: CellKind<T>,
+    /// Rendering hints given before the cell contents, see `CellFormat`.
+    pub format: Option<CellFormat>,
+}
+
+/// Rendering hints written as leading directives such as `{bold}{color:red}=a1*2`.
+/// They are removed from the cell's source and do not affect its value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CellFormat {
+    pub bold: bool,
+    pub italic: bool,
+    /// A color name or `#rrggbb` code.
+    pub color: Option<String>,
+}
+
+impl CellFormat {
+    /// Splits the leading `{bold}`, `{italic}` and `{color:<name>}` directives
+    /// off `source`, returning `None` when there are none. Splitting stops at
+    /// anything else, so text such as `{note} hi` or `{x` is left as it is.
+    pub fn split_directives(source: Cow<'_, str>) -> (Option<Self>, Cow<'_, str>) {
+        let mut format: Option<Self> = None;
+        let mut start = 0;
+        while let Some(directive) = source[start..].strip_prefix('{') {
+            let Some(end) = directive.find('}') else {
+                break;
+            };
+            let directive = directive[..end].to_ascii_lowercase();
+            let mut next = format.clone().unwrap_or_default();
+            match directive.split_once(':') {
+                None if directive == "bold" => next.bold = true,
+                None if directive == "italic" => next.italic = true,
+                Some(("color", color))
+                    if !color.is_empty()
+                        && color
+                            .strip_prefix('#')
+                            .unwrap_or(color)
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric()) =>
+                {
+                    next.color = Some(color.into())
+                }
+                _ => break,
+            }
+            format = Some(next);
+            start += end + 2;
+        }
+        let rest = match source {
+            Cow::Borrowed(s) => Cow::Borrowed(&s[start..]),
+            Cow::Owned(s) if start == 0 => Cow::Owned(s),
+            Cow::Owned(s) => Cow::Owned(s[start..].into()),
+        };
+        (format, rest)
+    }
+}
+
+/// Options controlling how cell sources are interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Characters that mark a cell as a formula, e.g. `=` or the Lotus-style
+    /// `+` and `@`. A `-` prefix is kept as part of the formula, so `-a1`
+    /// negates `a1`.
+    pub formula_prefixes: Vec<char>,
+    /// Rejects any cell that starts with a number but continues with something
+    /// else, naming the unexpected suffix. This includes cells that a `-` or `+`
+    /// formula prefix would otherwise accept as formulas, such as `-3+4`.
+    pub strict_numbers: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            formula_prefixes: vec!['='],
+            strict_numbers: false,
+        }
+    }
 }
 
 impl<'a> Cell<'a, Expr> {
-    pub fn new_expr(source: &'a str) -> TableResult<Self> {
+    pub fn new_expr(source: impl Into<Cow<'a, str>>) -> TableResult<Self> {
+        Self::new_expr_with_options(source, &ParseOptions::default())
+    }
+
+    /// Parses `source` like `new_expr`, recognising formulas by any of the
+    /// configured prefixes. Signed numbers such as `+5` or `-5` stay numbers,
+    /// and ISO dates such as `2024-03-15` are text, as if quoted.
+    /// Leading formatting directives are split off into `format`.
+    pub fn new_expr_with_options(
+        source: impl Into<Cow<'a, str>>,
+        options: &ParseOptions,
+    ) -> TableResult<Self> {
+        let (format, source) = CellFormat::split_directives(source.into());
+        let is_date = Date::parse_iso(&source).is_ok();
+        if options.strict_numbers && !is_date {
+            reject_partial_number(&source)?;
+        }
         let token_stream = source.chars().collect::<Vec<_>>();
-        let kind = if token_stream.len() == 0 {
+        let kind = if token_stream.is_empty() {
             CellKind::Empty
         } else {
             match token_stream[0] {
-                '=' => parse_expr(&token_stream[1..]),
+                '\'' => Ok(CellKind::Text(token_stream[1..].iter().collect())),
+                _ if source.eq_ignore_ascii_case("true") => Ok(CellKind::Bool(true)),
+                _ if source.eq_ignore_ascii_case("false") => Ok(CellKind::Bool(false)),
+                '+' | '-' if Number::from_str(&source).is_ok() => parse_number(&source),
+                '-' if options.formula_prefixes.contains(&'-') => parse_expr(&token_stream),
+                c if options.formula_prefixes.contains(&c) => parse_expr(&token_stream[1..]),
+                _ if is_date => Ok(CellKind::Text(source.clone().into_owned())),
                 c if c.is_numeric() => parse_number(&source),
-                _ => unimplemented!("Unimplemented cell kind"),
+                _ if Number::from_str(&source).is_ok() => parse_number(&source),
+                _ => Ok(CellKind::Text(source.clone().into_owned())),
             }?
         };
 
-        Ok(Self { source, kind })
+        Ok(Self {
+            source,
+            kind,
+            format,
+        })
+    }
+}
+
+impl<'a, T: Evaluate> Cell<'a, T> {
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self.kind,
+            CellKind::Expr {
+                result: Some(Err(_)),
+                ..
+            }
+        )
     }
 }
 
-impl<'a, T: Evaluate> std::fmt::Display for Cell<'a, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<'a, T: Evaluate> core::fmt::Display for Cell<'a, T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self.kind.clone() {
-            CellKind::Empty => write!(f, " "),
+            CellKind::Empty => Ok(()),
+            CellKind::Unparsed => write!(f, "{}", self.source),
             CellKind::Number(d) => write!(f, "{d}"),
+            CellKind::Text(s) => write!(f, "{s}"),
+            CellKind::Bool(b) => write!(f, "{b}"),
+            CellKind::Spilled { result, .. } => match result {
+                Err(e) => write!(f, "{e}"),
+                Ok(c) => write!(f, "{c}"),
+            },
             CellKind::Expr { result, .. } => match result {
                 None => write!(f, "{}", self.source),
                 Some(r) => match r {
@@ -72,19 +204,188 @@ impl<'a, T: Evaluate> std::fmt::Display for Cell<'a, T> {
 // impl<'a> Cell<'a, Expr> {
 //     pub fn evaluate<P>(mut self, evaluate_other: P) -> Self
 //     where
-//         P: FnMut((usize, usize)) -> TableResult<BigDecimal> + Clone,
+//         P: FnMut((usize, usize)) -> TableResult<Number> + Clone,
 //     {
 //     }
 // }
 
-fn parse_expr<'a>(token_stream: &'a [char]) -> TableResult<CellKind<Expr>> {
-    let mut tokenizer = Tokenizer::new(&token_stream);
+fn parse_expr(token_stream: &[char]) -> TableResult<CellKind<Expr>> {
+    let mut tokenizer = Tokenizer::new(token_stream);
     let mut parser = Parser::new(&mut tokenizer);
-    parser.ast().map(|ast| CellKind::new_expr(ast))
+    parser.ast().map(CellKind::new_expr)
 }
 
-fn parse_number<'a>(num: &'a str) -> TableResult<CellKind<Expr>> {
-    BigDecimal::from_str(num)
+/// Errors if `source` starts with a number but is not one as a whole.
+fn reject_partial_number(source: &str) -> TableResult<()> {
+    if Number::from_str(source).is_ok() {
+        return Ok(());
+    }
+    let unsigned = source.strip_prefix(['+', '-']).unwrap_or(source);
+    let digits = unsigned
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(unsigned.len());
+    if digits == 0 {
+        return Ok(());
+    }
+    let mut end = source.len() - unsigned.len() + digits;
+    if let Some(fraction) = source[end..].strip_prefix('.') {
+        end += 1 + fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+    }
+    Err(TableError::InvalidCell(format!(
+        "Unexpected \"{}\" after number {} in {source}",
+        &source[end..],
+        &source[..end]
+    )))
+}
+
+fn parse_number(num: &str) -> TableResult<CellKind<Expr>> {
+    Number::from_str(num)
         .map_err(|_| TableError::InvalidCell(format!("Could not format {num} as a valid number")))
-        .map(|d| CellKind::new_number(d))
+        .map(CellKind::new_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unquoted_date() {
+        let cell = Cell::new_expr("2024-03-15").unwrap();
+        assert!(matches!(cell.kind, CellKind::Text(ref s) if s == "2024-03-15"));
+
+        let strict = ParseOptions {
+            strict_numbers: true,
+            ..ParseOptions::default()
+        };
+        assert!(Cell::new_expr_with_options("2024-03-15", &strict).is_ok());
+        assert!(Cell::new_expr_with_options("2024-02-30", &strict).is_err());
+    }
+
+    #[test]
+    fn test_apostrophe_forces_text() {
+        let cell = Cell::new_expr("'007").unwrap();
+        assert!(matches!(cell.kind, CellKind::Text(ref s) if s == "007"));
+        assert_eq!(alloc::format!("{cell}"), "007");
+
+        let cell = Cell::new_expr("'=a1+1").unwrap();
+        assert!(matches!(cell.kind, CellKind::Text(ref s) if s == "=a1+1"));
+    }
+
+    #[test]
+    fn test_bare_text() {
+        for source in ["Total", "unit price", "a1", "@label", "#N/A"] {
+            let cell = Cell::new_expr(source).unwrap();
+            assert!(
+                matches!(cell.kind, CellKind::Text(ref s) if s == source),
+                "{source}"
+            );
+            assert_eq!(alloc::format!("{cell}"), source);
+        }
+        assert!(matches!(
+            Cell::new_expr(".5").unwrap().kind,
+            CellKind::Number(_)
+        ));
+    }
+
+    #[test]
+    fn test_formatting_directives() {
+        let cell = Cell::new_expr("{bold}{Color:#FF0000}=1+2").unwrap();
+        assert_eq!(cell.source, "=1+2");
+        assert!(matches!(cell.kind, CellKind::Expr { .. }));
+        assert_eq!(
+            cell.format,
+            Some(CellFormat {
+                bold: true,
+                italic: false,
+                color: Some("#ff0000".into()),
+            })
+        );
+
+        let cell = Cell::new_expr("42").unwrap();
+        assert_eq!(cell.format, None);
+        let cell = Cell::new_expr("'{bold}").unwrap();
+        assert!(matches!(cell.kind, CellKind::Text(ref s) if s == "{bold}"));
+        assert_eq!(cell.format, None);
+
+        for source in [
+            "{bold",
+            "{underline}1",
+            "{color:}1",
+            "{color:red;x}1",
+            "{note} hi",
+        ] {
+            let cell = Cell::new_expr(source).unwrap();
+            assert!(
+                matches!(cell.kind, CellKind::Text(ref s) if s == source),
+                "{source}"
+            );
+            assert_eq!(cell.format, None);
+        }
+
+        let cell = Cell::new_expr("{italic}{note} hi").unwrap();
+        assert!(matches!(cell.kind, CellKind::Text(ref s) if s == "{note} hi"));
+        assert!(cell.format.is_some_and(|format| format.italic));
+    }
+
+    #[test]
+    fn test_partly_numeric_cells() {
+        // Never truncated to their numeric prefix, even without strict mode.
+        for source in ["12abc", "3+4"] {
+            assert!(
+                matches!(Cell::new_expr(source), Err(TableError::InvalidCell(_))),
+                "{source}"
+            );
+        }
+
+        let lotus = |strict_numbers| ParseOptions {
+            formula_prefixes: vec!['=', '-'],
+            strict_numbers,
+        };
+        assert!(Cell::new_expr_with_options("-3+4", &lotus(false)).is_ok());
+        for (source, message) in [
+            ("12abc", "Unexpected \"abc\" after number 12 in 12abc"),
+            ("3+4", "Unexpected \"+4\" after number 3 in 3+4"),
+            ("-3+4", "Unexpected \"+4\" after number -3 in -3+4"),
+            ("1.5.2", "Unexpected \".2\" after number 1.5 in 1.5.2"),
+        ] {
+            match Cell::new_expr_with_options(source, &lotus(true)) {
+                Err(TableError::InvalidCell(msg)) => assert_eq!(msg, message),
+                other => panic!("expected {source} to be rejected, got {other:?}"),
+            }
+        }
+        for source in ["12", "-1.5", "1e3", "=a1+1", "-a1", "'12abc"] {
+            assert!(
+                Cell::new_expr_with_options(source, &lotus(true)).is_ok(),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_formula_prefixes() {
+        let options = ParseOptions {
+            formula_prefixes: vec!['=', '+', '@', '-'],
+            ..ParseOptions::default()
+        };
+        for source in ["=a1+b1", "+a1+b1", "@sum(a1:a5)", "-a1"] {
+            let cell = Cell::new_expr_with_options(source, &options).unwrap();
+            assert!(matches!(cell.kind, CellKind::Expr { .. }), "{source}");
+        }
+
+        let cell = Cell::new_expr_with_options("-a1", &options).unwrap();
+        let CellKind::Expr { expr, .. } = cell.kind else {
+            panic!("expected a formula");
+        };
+        assert_eq!(alloc::format!("{expr}"), "-a1");
+
+        for (source, expected) in [("+5", "5"), ("-5", "-5"), ("-1.5", "-1.5")] {
+            let cell = Cell::new_expr_with_options(source, &options).unwrap();
+            assert!(
+                matches!(cell.kind, CellKind::Number(ref d) if *d == Number::from_str(expected).unwrap()),
+                "{source}"
+            );
+        }
+    }
 }
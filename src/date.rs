@@ -0,0 +1,105 @@
+use super::error::{TableError, TableResult};
+use alloc::format;
+
+/// A day in the proleptic Gregorian calendar. Dates are written in cells as
+/// ISO `YYYY-MM-DD` text, e.g. `2024-03-15` or the quoted `'2024-03-15`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Parses an ISO `YYYY-MM-DD` date, rejecting days that do not exist.
+    pub fn parse_iso(s: &str) -> TableResult<Self> {
+        let invalid =
+            || TableError::runtime_error(format!("Expected a YYYY-MM-DD date, got \"{s}\""));
+        let bytes = s.as_bytes();
+        if bytes.len() != 10
+            || !s.is_ascii()
+            || bytes[4] != b'-'
+            || bytes[7] != b'-'
+            || !s[..4]
+                .bytes()
+                .chain(s[5..7].bytes())
+                .chain(s[8..].bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let date = Date {
+            year: s[..4].parse().map_err(|_| invalid())?,
+            month: s[5..7].parse().map_err(|_| invalid())?,
+            day: s[8..].parse().map_err(|_| invalid())?,
+        };
+        if !(1..=12).contains(&date.month) || date.day == 0 || date.day > date.days_in_month() {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+
+    /// The day of the week, from 1 for Sunday to 7 for Saturday.
+    pub fn weekday(&self) -> u32 {
+        // 1970-01-01 was a Thursday.
+        ((self.days_since_epoch() + 4).rem_euclid(7) + 1) as u32
+    }
+
+    fn is_leap_year(&self) -> bool {
+        self.year % 4 == 0 && (self.year % 100 != 0 || self.year % 400 == 0)
+    }
+
+    fn days_in_month(&self) -> u32 {
+        match self.month {
+            2 if self.is_leap_year() => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        }
+    }
+
+    /// Days from 1970-01-01, negative before it.
+    fn days_since_epoch(&self) -> i64 {
+        // Counts from March so that the leap day falls at the end of the year.
+        let (month, day) = (i64::from(self.month), i64::from(self.day));
+        let year = if month <= 2 { self.year - 1 } else { self.year };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_iso() {
+        let date = Date::parse_iso("2024-03-15").unwrap();
+        assert_eq!((date.year, date.month, date.day), (2024, 3, 15));
+        assert!(Date::parse_iso("2024-02-29").is_ok());
+        for invalid in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-04-31",
+            "2024-3-15",
+            "15/03/2024",
+            "2024-03-1x",
+            "2024-03-é",
+        ] {
+            assert!(Date::parse_iso(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_weekday() {
+        let weekday = |s| Date::parse_iso(s).unwrap().weekday();
+        assert_eq!(weekday("1970-01-01"), 5);
+        assert_eq!(weekday("2024-03-15"), 6);
+        assert_eq!(weekday("2024-03-17"), 1);
+        assert_eq!(weekday("2000-02-29"), 3);
+        assert_eq!(weekday("1900-01-01"), 2);
+    }
+}
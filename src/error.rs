@@ -1,10 +1,17 @@
-use std::result::Result;
+use crate::tokenizer::cell_name;
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::result::Result;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Error)]
 pub enum TableError {
     #[error("Mismatched column")]
     MismatchedColumns,
+    #[error("Mismatched rows")]
+    MismatchedRows,
     #[error("Empty table")]
     EmptyTable,
     #[error("Error reading file")]
@@ -15,12 +22,19 @@ pub enum TableError {
     ErrorConstructingAst(String),
     #[error("Runtime Error: {0}")]
     RuntimeError(String),
-    #[error("Recursive Cell at: {0:?}")]
-    RecursiveCellExpr((usize, usize)),
+    /// The cells of the cycle in reference order, ending where it started.
+    #[error("Recursive Cell at: {}", cycle_path(.0))]
+    RecursiveCellExpr(Vec<(usize, usize)>),
     #[error("Error attempting to evaluate empty cell")]
     EmptyCellEvaluation,
     #[error("Multiple cell values returned where a single was expected")]
     MultipleCellReturn,
+    #[error("Spill blocked at: {0:?}")]
+    SpillConflict((usize, usize)),
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Reference to a cell outside the table: {0:?}")]
+    CellOutOfBounds((usize, usize)),
 }
 
 impl TableError {
@@ -30,6 +44,65 @@ impl TableError {
     {
         Self::RuntimeError(err.to_string())
     }
+
+    /// A stable numeric code for the kind of error, for callers that cannot
+    /// match on the enum (FFI, structured logs). Codes are never reused or
+    /// renumbered; new variants get the next free code.
+    pub fn code(&self) -> u32 {
+        match self {
+            Self::MismatchedColumns => 1,
+            Self::MismatchedRows => 2,
+            Self::EmptyTable => 3,
+            Self::ErrorReadingFile => 4,
+            Self::InvalidCell(_) => 5,
+            Self::ErrorConstructingAst(_) => 6,
+            Self::RuntimeError(_) => 7,
+            Self::RecursiveCellExpr(_) => 8,
+            Self::EmptyCellEvaluation => 9,
+            Self::MultipleCellReturn => 10,
+            Self::SpillConflict(_) => 11,
+            Self::DivisionByZero => 12,
+            Self::CellOutOfBounds(_) => 13,
+        }
+    }
 }
 
 pub type TableResult<T> = Result<T, TableError>;
+
+/// Formats a cycle of cells as `a1 -> b2 -> a1`.
+fn cycle_path(cells: &[(usize, usize)]) -> String {
+    cells
+        .iter()
+        .map(|&(row, col)| cell_name(row, col))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{collections::BTreeSet, vec};
+
+    #[test]
+    fn test_codes_are_distinct() {
+        let errors = vec![
+            TableError::MismatchedColumns,
+            TableError::MismatchedRows,
+            TableError::EmptyTable,
+            TableError::ErrorReadingFile,
+            TableError::InvalidCell(String::new()),
+            TableError::ErrorConstructingAst(String::new()),
+            TableError::RuntimeError(String::new()),
+            TableError::RecursiveCellExpr(vec![(0, 0)]),
+            TableError::EmptyCellEvaluation,
+            TableError::MultipleCellReturn,
+            TableError::SpillConflict((0, 0)),
+            TableError::DivisionByZero,
+            TableError::CellOutOfBounds((0, 0)),
+        ];
+        let codes = errors.iter().map(TableError::code).collect::<BTreeSet<_>>();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0));
+        assert_eq!(TableError::runtime_error("x").code(), 7);
+    }
+}
@@ -1,8 +1,144 @@
-use super::error::TableResult;
-use bigdecimal::BigDecimal;
+use super::error::{TableError, TableResult};
+use super::number::Number;
+use super::value::Value;
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
+#[cfg(not(feature = "fast-float"))]
+use bigdecimal::Signed;
+use bigdecimal::{RoundingMode, ToPrimitive};
+use core::num::NonZeroU64;
 
-pub trait Evaluate: Clone + std::fmt::Debug + std::marker::Send {
-    fn evaluate<P>(&self, get_cell_value: &mut P) -> Vec<TableResult<BigDecimal>>
+/// A cap on the significant digits of multiplication results, so chains of
+/// products cannot grow a number's precision without bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PrecisionBudget {
+    pub max_digits: NonZeroU64,
+    /// Fail instead of rounding when a result does not fit in `max_digits`.
+    pub strict: bool,
+}
+
+/// Table-level settings consulted while evaluating formulas.
+#[derive(Debug, Clone)]
+pub struct EvalContext {
+    /// When set, arithmetic results are clamped into `[min, max]`.
+    pub clamp: Option<(Number, Number)>,
+    /// Values for names used in formulas, keyed by lowercase name. A variable
+    /// named like a cell (e.g. `a1`) takes precedence over the cell.
+    pub variables: BTreeMap<String, Number>,
+    /// How `round` and division results are rounded. Defaults to half-up.
+    pub rounding: RoundingMode,
+    /// When set, division results are rounded to this many decimal places.
+    pub division_scale: Option<i64>,
+    /// The cell each `@label` currently refers to, keyed by lowercase label.
+    pub labels: BTreeMap<String, (usize, usize)>,
+    /// When set, multiplication results are rounded to this many significant digits.
+    pub precision: Option<PrecisionBudget>,
+    /// Cell values as of the previous `Table::run_step`, read by `prev`.
+    pub previous: BTreeMap<(usize, usize), Value>,
+    /// Whether arithmetic reads numeric text such as `"42"` as a number rather
+    /// than failing.
+    pub coerce_text_numbers: bool,
+    /// When set, cell ranges are cut to this many rows and columns, so a range
+    /// reaching past the table only covers the cells inside it. Otherwise the
+    /// cells outside it evaluate to `CellOutOfBounds`.
+    pub range_bounds: Option<(usize, usize)>,
+}
+
+impl Default for EvalContext {
+    fn default() -> Self {
+        Self {
+            clamp: None,
+            variables: BTreeMap::new(),
+            rounding: RoundingMode::HalfUp,
+            division_scale: None,
+            labels: BTreeMap::new(),
+            precision: None,
+            previous: BTreeMap::new(),
+            coerce_text_numbers: false,
+            range_bounds: None,
+        }
+    }
+}
+
+impl EvalContext {
+    pub fn clamp(&self, d: Number) -> Number {
+        match &self.clamp {
+            Some((min, _)) if d < *min => min.clone(),
+            Some((_, max)) if d > *max => max.clone(),
+            _ => d,
+        }
+    }
+
+    /// Rounds a product to the precision budget, if one is set. With a strict
+    /// budget a product that would lose digits is an error. `fast-float`
+    /// numbers have a fixed precision, so there is nothing to limit.
+    pub fn limit_precision(&self, d: Number) -> TableResult<Number> {
+        #[cfg(not(feature = "fast-float"))]
+        if let Some(budget) = self.precision {
+            if d.digits() > budget.max_digits.get() {
+                let rounded = d.with_precision_round(budget.max_digits, self.rounding);
+                if budget.strict && rounded != d {
+                    return Err(TableError::runtime_error(format!(
+                        "Result {d} does not fit in {} significant digits",
+                        budget.max_digits
+                    )));
+                }
+                return Ok(rounded);
+            }
+        }
+        Ok(d)
+    }
+
+    /// Rounds a quotient to `division_scale`, if one is set.
+    pub fn round_quotient(&self, d: Number) -> Number {
+        match self.division_scale {
+            Some(scale) => d.with_scale_round(scale, self.rounding),
+            None => d,
+        }
+    }
+}
+
+/// Converts a formula value into an index, rejecting negative, fractional
+/// and out of `usize` range values instead of truncating or wrapping them.
+pub fn bigdecimal_to_index(d: &Number) -> TableResult<usize> {
+    if d.is_negative() {
+        return Err(TableError::runtime_error(format!(
+            "Expected a non-negative index, got {d}"
+        )));
+    }
+    if !d.is_integer() {
+        return Err(TableError::runtime_error(format!(
+            "Expected a whole number index, got {d}"
+        )));
+    }
+    d.to_usize()
+        .ok_or_else(|| TableError::runtime_error(format!("Index {d} is too large")))
+}
+
+pub trait Evaluate: Clone + core::fmt::Debug + core::marker::Send {
+    fn evaluate<P>(&self, context: &EvalContext, get_cell_value: &mut P) -> Vec<TableResult<Value>>
     where
-        P: FnMut(usize, usize) -> TableResult<BigDecimal>;
+        P: FnMut(usize, usize) -> TableResult<Value>;
+
+    /// Cells referenced directly by this expression, in the order evaluation visits them.
+    fn references(&self) -> Vec<(usize, usize)>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_bigdecimal_to_index() {
+        let index = |s: &str| bigdecimal_to_index(&Number::from_str(s).unwrap());
+        assert_eq!(index("3").unwrap(), 3);
+        assert_eq!(index("3.00").unwrap(), 3);
+        assert_eq!(index("0").unwrap(), 0);
+        for invalid in ["-1", "1.5", "-0.5", "1e30", "184467440737095516160"] {
+            assert!(
+                matches!(index(invalid), Err(TableError::RuntimeError(_))),
+                "{invalid} should not convert"
+            );
+        }
+    }
 }
@@ -0,0 +1,98 @@
+use super::error::{TableError, TableResult};
+use super::number::Number;
+use alloc::{format, string::String, vec::Vec};
+use bigdecimal::RoundingMode;
+
+/// Formats `d` with a spreadsheet number pattern such as `0.00` or `#,##0.0#`.
+///
+/// `0` is a digit that is always shown, `#` a digit shown only when needed, `.`
+/// separates the decimal places and a `,` anywhere in the integer part groups
+/// thousands.
+pub fn format_number(d: &Number, pattern: &str, rounding: RoundingMode) -> TableResult<String> {
+    if let Some(c) = pattern
+        .chars()
+        .find(|c| !matches!(c, '0' | '#' | '.' | ','))
+    {
+        return Err(TableError::runtime_error(format!(
+            "Unsupported character '{c}' in format pattern \"{pattern}\""
+        )));
+    }
+    let (integer_pattern, fraction_pattern) = match pattern.split_once('.') {
+        Some((_, fraction)) if fraction.contains(['.', ',']) => {
+            return Err(TableError::runtime_error(format!(
+                "Invalid format pattern \"{pattern}\""
+            )))
+        }
+        Some((integer, fraction)) => (integer, fraction),
+        None => (pattern, ""),
+    };
+    let min_integer_digits = integer_pattern.matches('0').count();
+    let min_decimals = fraction_pattern.matches('0').count();
+    let max_decimals = fraction_pattern.len();
+    let grouped = integer_pattern.contains(',');
+
+    let rounded = d
+        .with_scale_round(max_decimals as i64, rounding)
+        .to_plain_string();
+    let (negative, digits) = match rounded.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, rounded.as_str()),
+    };
+    let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+    let integer = integer.trim_start_matches('0');
+    let mut integer = String::from(integer);
+    while integer.len() < min_integer_digits {
+        integer.insert(0, '0');
+    }
+    let mut fraction = String::from(fraction);
+    while fraction.len() > min_decimals && fraction.ends_with('0') {
+        fraction.pop();
+    }
+    while fraction.len() < min_decimals {
+        fraction.push('0');
+    }
+
+    let mut formatted = String::new();
+    if negative && (integer.chars().chain(fraction.chars())).any(|c| c != '0') {
+        formatted.push('-');
+    }
+    if grouped {
+        let digits = integer.chars().collect::<Vec<_>>();
+        for (i, c) in digits.iter().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                formatted.push(',');
+            }
+            formatted.push(*c);
+        }
+    } else {
+        formatted.push_str(&integer);
+    }
+    if !fraction.is_empty() {
+        formatted.push('.');
+        formatted.push_str(&fraction);
+    }
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_format_number() {
+        let format = |d: &str, pattern: &str| {
+            format_number(&Number::from_str(d).unwrap(), pattern, RoundingMode::HalfUp)
+        };
+        assert_eq!(format("1234.5", "0.00").unwrap(), "1234.50");
+        assert_eq!(format("1234567.891", "#,##0.00").unwrap(), "1,234,567.89");
+        assert_eq!(format("-1234.5", "#,##0").unwrap(), "-1,235");
+        assert_eq!(format("0.5", "#.##").unwrap(), ".5");
+        assert_eq!(format("0.5", "0.0#").unwrap(), "0.5");
+        assert_eq!(format("7", "000").unwrap(), "007");
+        assert_eq!(format("-0.001", "0.00").unwrap(), "0.00");
+        assert!(format("1", "0.00 USD").is_err());
+        assert!(format("1", "0.0.0").is_err());
+    }
+}
@@ -1,4 +1,5 @@
-use std::{
+use alloc::vec::Vec;
+use core::{
     fmt::Debug,
     ops::{Index, IndexMut},
 };
@@ -18,11 +19,87 @@ impl<T: Debug + Clone> Grid<T> {
         Self {
             rows,
             cols,
-            internal: internal,
+            internal,
+        }
+    }
+
+    /// The cell at `(row, col)`, or `None` when it is outside the grid.
+    pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+        if row >= self.rows || col >= self.cols {
+            return None;
+        }
+        self.internal.get(self.cols * row + col)
+    }
+
+    /// Inserts a row of `fill` before row `at`, moving the rows from `at` down by one.
+    pub fn insert_row(&mut self, at: usize, fill: T) {
+        let start = at.min(self.rows) * self.cols;
+        self.internal
+            .splice(start..start, core::iter::repeat_n(fill, self.cols));
+        self.rows += 1;
+    }
+
+    /// The grid with rows and columns swapped.
+    pub fn transpose(&self) -> Self {
+        let internal = (0..self.cols)
+            .flat_map(|col| (0..self.rows).map(move |row| (row, col)))
+            .map(|coord| self[coord].clone())
+            .collect();
+        Grid::new(self.cols, self.rows, internal)
+    }
+
+    /// Inserts a column of `fill` before column `at`, moving the columns from `at`
+    /// right by one.
+    pub fn insert_col(&mut self, at: usize, fill: T) {
+        let at = at.min(self.cols);
+        let mut internal = Vec::with_capacity(self.rows * (self.cols + 1));
+        for row in self.internal.chunks(self.cols.max(1)) {
+            internal.extend_from_slice(&row[..at]);
+            internal.push(fill.clone());
+            internal.extend_from_slice(&row[at..]);
+        }
+        self.internal = internal;
+        self.cols += 1;
+    }
+
+    pub fn map<U, F>(self, f: F) -> Grid<U>
+    where
+        U: Debug + Clone,
+        F: FnMut(T) -> U,
+    {
+        Grid {
+            internal: self.internal.into_iter().map(f).collect(),
+            rows: self.rows,
+            cols: self.cols,
         }
     }
 }
 
+impl<T: Debug + Clone + PartialEq> Grid<T> {
+    /// Coordinates and values of every cell where `self` and `other` differ.
+    ///
+    /// # Panics
+    ///
+    /// If the two grids do not have the same dimensions.
+    pub fn diff<'a>(&'a self, other: &'a Grid<T>) -> Vec<((usize, usize), &'a T, &'a T)> {
+        assert_eq!(
+            (self.rows, self.cols),
+            (other.rows, other.cols),
+            "cannot diff grids of different sizes"
+        );
+        let mut differences = Vec::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let (left, right) = (&self[(row, col)], &other[(row, col)]);
+                if left != right {
+                    differences.push(((row, col), left, right));
+                }
+            }
+        }
+        differences
+    }
+}
+
 impl<T: Debug + Clone> Index<(usize, usize)> for Grid<T> {
     type Output = T;
     fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
@@ -35,3 +112,58 @@ impl<T: Debug + Clone> IndexMut<(usize, usize)> for Grid<T> {
         &mut self.internal[(self.cols * row) + col]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_get() {
+        let grid = Grid::new(2, 3, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(grid.get(1, 2), Some(&6));
+        assert_eq!(grid.get(0, 3), None);
+        assert_eq!(grid.get(2, 0), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        let left = Grid::new(2, 2, vec![1, 2, 3, 4]);
+        let right = Grid::new(2, 2, vec![1, 2, 5, 4]);
+        assert_eq!(left.diff(&right), vec![((1, 0), &3, &5)]);
+        assert!(left.diff(&left.clone()).is_empty());
+    }
+
+    #[test]
+    fn test_transpose() {
+        let grid = Grid::new(2, 3, vec![1, 2, 3, 4, 5, 6]);
+        let transposed = grid.transpose();
+        assert_eq!((transposed.rows, transposed.cols), (3, 2));
+        assert_eq!(transposed[(0, 1)], 4);
+        assert_eq!(transposed[(2, 0)], 3);
+        assert!(transposed.transpose().diff(&grid).is_empty());
+    }
+
+    #[test]
+    fn test_insert_col() {
+        let mut grid = Grid::new(2, 2, vec![1, 2, 3, 4]);
+        grid.insert_col(1, 0);
+        assert_eq!((grid.rows, grid.cols), (2, 3));
+        assert!(grid
+            .diff(&Grid::new(2, 3, vec![1, 0, 2, 3, 0, 4]))
+            .is_empty());
+        grid.insert_col(3, 9);
+        assert_eq!(grid[(1, 3)], 9);
+    }
+
+    #[test]
+    fn test_insert_row() {
+        let mut grid = Grid::new(2, 2, vec![1, 2, 3, 4]);
+        grid.insert_row(1, 0);
+        assert_eq!((grid.rows, grid.cols), (3, 2));
+        assert_eq!(grid[(1, 1)], 0);
+        assert_eq!(grid[(2, 0)], 3);
+        grid.insert_row(3, 9);
+        assert_eq!(grid[(3, 0)], 9);
+    }
+}
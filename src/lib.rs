@@ -0,0 +1,48 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod ast;
+pub mod cell;
+pub mod date;
+pub mod error;
+pub mod eval;
+pub mod format;
+pub mod grid;
+pub mod number;
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod stream;
+#[cfg(feature = "std")]
+pub mod table;
+pub mod tokenizer;
+pub mod value;
+
+use ast::Expr;
+use error::TableResult;
+
+/// Tokenizes and parses a single formula, written without the leading `=`.
+pub fn parse_formula(source: &str) -> TableResult<Expr> {
+    let chars = source.chars().collect::<alloc::vec::Vec<_>>();
+    let mut tokenizer = tokenizer::Tokenizer::new(&chars);
+    parser::Parser::new(&mut tokenizer).ast()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokenizer::Token;
+
+    #[test]
+    fn test_parse_formula() {
+        assert_eq!(
+            parse_formula("a1+b1").unwrap(),
+            Expr::binary(
+                Expr::literal(Token::CellRef((0, 0).into())),
+                Token::Plus,
+                Expr::literal(Token::CellRef((0, 1).into())),
+            )
+        );
+        assert!(parse_formula("(a1").is_err());
+    }
+}
@@ -1,31 +1,50 @@
-#[allow(unused_variables)]
-#[allow(dead_code)]
-#[allow(unused)]
 use std::fs::File;
 use std::io::{BufReader, Read};
 
-mod cell;
-mod error;
-use error::{TableError, TableResult};
-mod ast;
-mod eval;
-mod parser;
-mod table;
-mod tokenizer;
-use table::Table;
-mod grid;
+use rxl::error::{TableError, TableResult};
+use rxl::table::Table;
 
-fn main() -> TableResult<()> {
-    let f = File::open("./input.rxl").map_err(|_| TableError::ErrorReadingFile)?;
+fn read_source(path: &str) -> TableResult<String> {
+    let f = File::open(path).map_err(|_| TableError::ErrorReadingFile)?;
     let mut reader = BufReader::new(f);
     let mut buf = String::new();
     reader
         .read_to_string(&mut buf)
         .map_err(|_| TableError::ErrorReadingFile)?;
+    Ok(buf)
+}
+
+/// Usage: `rxl [--vertical | --horizontal] [FILE...]`, defaulting to `./input.rxl`.
+/// Several files are merged by stacking them (`--vertical`, the default) or
+/// placing them side by side (`--horizontal`).
+fn main() -> TableResult<()> {
+    let mut horizontal = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--vertical" => horizontal = false,
+            "--horizontal" => horizontal = true,
+            _ => paths.push(arg),
+        }
+    }
+    if paths.is_empty() {
+        paths.push("./input.rxl".to_string());
+    }
 
-    let chars = buf.chars().collect::<String>();
+    let sources = paths
+        .iter()
+        .map(|p| read_source(p))
+        .collect::<TableResult<Vec<_>>>()?;
+    let tables = sources
+        .iter()
+        .map(|s| Table::new_interpet(s))
+        .collect::<TableResult<Vec<_>>>()?;
 
-    let mut table = Table::new_interpet(&chars)?;
+    let mut table = if horizontal {
+        Table::concat_horizontal(&tables)?
+    } else {
+        Table::concat_vertical(&tables)?
+    };
     println!("{}", table);
     table.run();
     println!("{}", table);
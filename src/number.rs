@@ -0,0 +1,219 @@
+//! The numeric type cells evaluate to.
+//!
+//! By default this is the exact `BigDecimal`. With the `fast-float` feature it
+//! is `Float`, an `f64` wrapper providing the parts of the `BigDecimal` API the
+//! evaluator uses: much faster on large numeric sheets, but decimal fractions
+//! such as `0.1` are only approximated, so results like `0.1 + 0.2` can differ
+//! from the exact answer in the last few digits.
+
+#[cfg(not(feature = "fast-float"))]
+pub type Number = bigdecimal::BigDecimal;
+
+#[cfg(feature = "fast-float")]
+pub type Number = Float;
+
+#[cfg(feature = "fast-float")]
+pub use float::Float;
+
+#[cfg(feature = "fast-float")]
+use crate::error::TableError;
+use crate::error::TableResult;
+
+/// Rejects the infinities and NaN that `fast-float` arithmetic can produce, as
+/// they have no `BigDecimal` counterpart. Exact numbers are always finite.
+pub fn ensure_finite(d: Number) -> TableResult<Number> {
+    #[cfg(feature = "fast-float")]
+    if !d.0.is_finite() {
+        return Err(TableError::runtime_error(alloc::format!(
+            "{d} is not representable"
+        )));
+    }
+    Ok(d)
+}
+
+#[cfg(feature = "fast-float")]
+mod float {
+    use alloc::string::{String, ToString};
+    use bigdecimal::{BigDecimal, FromPrimitive, RoundingMode, ToPrimitive};
+    use core::{cmp::Ordering, str::FromStr};
+
+    /// An `f64` with a total order, so it can be used wherever `BigDecimal` is.
+    #[derive(Debug, Clone, Default)]
+    pub struct Float(pub f64);
+
+    impl Float {
+        pub fn is_negative(&self) -> bool {
+            self.0 < 0.0
+        }
+
+        pub fn is_zero(&self) -> bool {
+            self.0 == 0.0
+        }
+
+        pub fn is_integer(&self) -> bool {
+            self.0.is_finite() && self.0 % 1.0 == 0.0
+        }
+
+        pub fn abs(&self) -> Self {
+            Float(if self.0 < 0.0 { -self.0 } else { self.0 })
+        }
+
+        /// Rounds to `scale` decimal places, going through `BigDecimal` so that
+        /// every `RoundingMode` behaves exactly as in the default build.
+        pub fn with_scale_round(&self, scale: i64, mode: RoundingMode) -> Self {
+            match BigDecimal::from_f64(self.0) {
+                Some(d) => Float(d.with_scale_round(scale, mode).to_f64().unwrap_or(self.0)),
+                None => self.clone(),
+            }
+        }
+
+        pub fn round(&self, digits: i64) -> Self {
+            self.with_scale_round(digits, RoundingMode::HalfEven)
+        }
+
+        pub fn to_plain_string(&self) -> String {
+            self.to_string()
+        }
+    }
+
+    impl ToPrimitive for Float {
+        fn to_i64(&self) -> Option<i64> {
+            self.0.to_i64()
+        }
+
+        fn to_u64(&self) -> Option<u64> {
+            self.0.to_u64()
+        }
+
+        fn to_f64(&self) -> Option<f64> {
+            Some(self.0)
+        }
+    }
+
+    impl PartialEq for Float {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Float {}
+
+    impl PartialOrd for Float {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Float {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0
+                .partial_cmp(&other.0)
+                .unwrap_or_else(|| self.0.total_cmp(&other.0))
+        }
+    }
+
+    impl core::fmt::Display for Float {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl FromStr for Float {
+        type Err = ParseFloatError;
+
+        /// Parses an `f64`, rejecting `inf`, `NaN` and values that overflow to
+        /// infinity such as `1e999`, which `BigDecimal` would not accept either.
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.parse::<f64>() {
+                Ok(f) if f.is_finite() => Ok(Float(f)),
+                _ => Err(ParseFloatError),
+            }
+        }
+    }
+
+    /// The error from parsing a `Float` that is malformed or not finite.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct ParseFloatError;
+
+    impl core::fmt::Display for ParseFloatError {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            write!(f, "invalid or non-finite number")
+        }
+    }
+
+    macro_rules! from_primitive {
+        ($($t:ty),*) => {
+            $(
+                impl From<$t> for Float {
+                    fn from(n: $t) -> Self {
+                        Float(n as f64)
+                    }
+                }
+            )*
+        };
+    }
+
+    from_primitive!(i8, i16, i32, i64, u8, u16, u32, u64, usize);
+
+    macro_rules! binary_op {
+        ($($trait:ident, $method:ident, $assign:ident, $assign_method:ident, $op:tt);*) => {
+            $(
+                impl core::ops::$trait for Float {
+                    type Output = Float;
+                    fn $method(self, rhs: Float) -> Float {
+                        Float(self.0 $op rhs.0)
+                    }
+                }
+
+                impl core::ops::$trait<&Float> for &Float {
+                    type Output = Float;
+                    fn $method(self, rhs: &Float) -> Float {
+                        Float(self.0 $op rhs.0)
+                    }
+                }
+
+                impl core::ops::$assign for Float {
+                    fn $assign_method(&mut self, rhs: Float) {
+                        self.0 = self.0 $op rhs.0;
+                    }
+                }
+            )*
+        };
+    }
+
+    binary_op!(
+        Add, add, AddAssign, add_assign, +;
+        Sub, sub, SubAssign, sub_assign, -;
+        Mul, mul, MulAssign, mul_assign, *;
+        Div, div, DivAssign, div_assign, /
+    );
+
+    impl core::ops::Neg for Float {
+        type Output = Float;
+        fn neg(self) -> Float {
+            Float(-self.0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::RoundingMode;
+    use core::str::FromStr;
+
+    #[test]
+    fn test_number_arithmetic() {
+        let n = |s: &str| Number::from_str(s).unwrap();
+        assert_eq!(n("1.5") + n("2.5"), Number::from(4));
+        assert_eq!(n("3") * n("-2"), Number::from(-6));
+        assert_eq!(n("7") / n("2"), n("3.5"));
+        assert!(n("-0.5") < n("0"));
+        assert!(n("4").is_integer());
+        assert!(n("1.25") < n("1.5"));
+        assert_eq!(
+            n("2.345").with_scale_round(2, RoundingMode::HalfUp),
+            n("2.35")
+        );
+    }
+}
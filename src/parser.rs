@@ -1,3 +1,5 @@
+use alloc::{format, string::ToString, vec::Vec};
+
 use crate::{
     ast::Expr,
     error::{TableError, TableResult},
@@ -22,18 +24,59 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
     fn get_previous_token(&mut self) -> TableResult<Token> {
         self.previous_token
             .clone()
-            .ok_or(TableError::ErrorConstructingAst(format!(
-                "Error returning previous token"
-            )))
+            .ok_or(TableError::ErrorConstructingAst(
+                "Error returning previous token".to_string(),
+            ))
     }
 
     pub fn ast(&mut self) -> TableResult<Expr> {
         self.advance()?;
-        self.expression()
+        let expr = self.expression()?;
+        match &self.current_token {
+            None => Ok(expr),
+            Some(token) => Err(TableError::ErrorConstructingAst(format!(
+                "Unexpected token after expression: {token}"
+            ))),
+        }
     }
 
     fn expression(&mut self) -> TableResult<Expr> {
-        self.term()
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> TableResult<Expr> {
+        use Token::{Equal, Greater, GreaterEqual, Less, LessEqual, NotEqual};
+        let mut expr = self.concatenation()?;
+        loop {
+            if !self.advance_match(|t| {
+                matches!(
+                    t,
+                    Less | LessEqual | Greater | GreaterEqual | Equal | NotEqual
+                )
+            })? {
+                return Ok(expr);
+            }
+
+            let operator = self.get_previous_token()?;
+
+            let right = self.concatenation()?;
+            expr = Expr::binary(expr, operator, right);
+        }
+    }
+
+    fn concatenation(&mut self) -> TableResult<Expr> {
+        use Token::Ampersand;
+        let mut expr = self.term()?;
+        loop {
+            if !self.advance_match(|t| t == Ampersand)? {
+                return Ok(expr);
+            }
+
+            let operator = self.get_previous_token()?;
+
+            let right = self.term()?;
+            expr = Expr::binary(expr, operator, right);
+        }
     }
 
     fn term(&mut self) -> TableResult<Expr> {
@@ -52,19 +95,32 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
     }
 
     fn factor(&mut self) -> TableResult<Expr> {
-        use Token::{Slash, Star};
-        let mut expr = self.unary()?;
+        use Token::{Slash, SlashSlash, Star};
+        let mut expr = self.power()?;
 
         loop {
-            if !self.advance_match(|t| t == Slash || t == Star)? {
+            if !self.advance_match(|t| t == Slash || t == SlashSlash || t == Star)? {
                 return Ok(expr);
             }
             let operator = self.get_previous_token()?;
-            let right = self.unary()?;
+            let right = self.power()?;
             expr = Expr::binary(expr, operator, right);
         }
     }
 
+    /// `^` is right-associative: the right operand is parsed by recursing here
+    /// rather than by folding left, so `2^3^2` is `2^(3^2)`.
+    fn power(&mut self) -> TableResult<Expr> {
+        use Token::Caret;
+        let expr = self.unary()?;
+        if !self.advance_match(|t| t == Caret)? {
+            return Ok(expr);
+        }
+        let operator = self.get_previous_token()?;
+        let right = self.power()?;
+        Ok(Expr::binary(expr, operator, right))
+    }
+
     fn unary(&mut self) -> TableResult<Expr> {
         use Token::Minus;
         if self.advance_match(|t| t == Minus)? {
@@ -72,10 +128,20 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
             let right = self.unary()?;
             Ok(Expr::unary(operator, right))
         } else {
-            self.call()
+            self.postfix()
         }
     }
 
+    fn postfix(&mut self) -> TableResult<Expr> {
+        use Token::Percent;
+        let mut expr = self.call()?;
+        while self.advance_match(|t| t == Percent)? {
+            let operator = self.get_previous_token()?;
+            expr = Expr::unary(operator, expr);
+        }
+        Ok(expr)
+    }
+
     fn call(&mut self) -> TableResult<Expr> {
         use Token::LeftParen;
         let mut expr = self.primary()?;
@@ -97,7 +163,7 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
         }
         self.consume_or(
             |t| t == RightParen,
-            TableError::ErrorConstructingAst(format!("Expect ')' after arguments")),
+            TableError::ErrorConstructingAst("Expect ')' after arguments".to_string()),
         )?;
 
         Ok(Expr::call(calle, arguments))
@@ -106,16 +172,28 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
     fn primary(&mut self) -> TableResult<Expr> {
         use Token::{LeftParen, RightParen};
         if self.advance_match(|t| {
-            t.is_number() || t.is_cell_ref() || t.is_builtin_fn() || t.is_cell_range()
+            t.is_number()
+                || t.is_bool()
+                || t.is_name()
+                || t.is_str()
+                || t.is_cell_ref()
+                || t.is_label()
+                || t.is_builtin_fn()
+                || t.is_cell_range()
         })? {
             let token = self.get_previous_token()?;
 
             Ok(Expr::literal(token))
         } else if self.advance_match(|t| t == LeftParen)? {
+            if self.current_token == Some(RightParen) {
+                return Err(TableError::ErrorConstructingAst(
+                    "empty parentheses".to_string(),
+                ));
+            }
             let expr = self.expression()?;
             self.consume_or(
                 |t| t == RightParen,
-                TableError::ErrorConstructingAst(format!("Expected ')' after expression")),
+                TableError::ErrorConstructingAst("Expected ')' after expression".to_string()),
             )?;
 
             Ok(Expr::grouping(expr))
@@ -168,8 +246,9 @@ impl<'source, I: Iterator<Item = TableResult<Token>>> Parser<'source, I> {
 mod tests {
     use super::*;
 
-    use bigdecimal::{BigDecimal, FromPrimitive};
-    use std::ops::Range;
+    use crate::number::Number;
+    use alloc::vec;
+    use core::{ops::Range, str::FromStr};
 
     struct DummyTokenizer {
         tokens: Vec<Token>,
@@ -197,11 +276,17 @@ mod tests {
 
     #[test]
     fn test_simple_op() {
-        use Token::{CellRef, Minus, Number, Plus, Slash, Star};
+        use Token::{CellRef, Minus, Plus, Slash, Star};
 
         for op in [Minus, Plus, Slash, Star] {
-            for left_token in [CellRef((0, 0)), Number(BigDecimal::from_f64(1.2).unwrap())] {
-                for right_token in [CellRef((1, 1)), Number(BigDecimal::from_f64(1.4).unwrap())] {
+            for left_token in [
+                CellRef((0, 0).into()),
+                Token::Number(Number::from_str("1.2").unwrap()),
+            ] {
+                for right_token in [
+                    CellRef((1, 1).into()),
+                    Token::Number(Number::from_str("1.4").unwrap()),
+                ] {
                     let tokens = vec![left_token.clone(), op.clone(), right_token.clone()];
                     let mut tokenizer = DummyTokenizer::new(tokens);
                     let mut parser = Parser::new(&mut tokenizer);
@@ -221,6 +306,70 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_concatenation() {
+        use crate::tokenizer::tokenize;
+        use Token::{Ampersand, CellRef, Equal, Plus};
+
+        let parse = |source: &str| {
+            let mut tokens = tokenize(source).unwrap().into_iter().map(Ok);
+            Parser::new(&mut tokens).ast().unwrap()
+        };
+        let cell = |col| Expr::literal(CellRef((0, col).into()));
+        assert_eq!(
+            parse("a1 & b1 + c1 & d1"),
+            Expr::binary(
+                Expr::binary(cell(0), Ampersand, Expr::binary(cell(1), Plus, cell(2))),
+                Ampersand,
+                cell(3)
+            )
+        );
+        assert_eq!(
+            parse("a1 & b1 = c1"),
+            Expr::binary(Expr::binary(cell(0), Ampersand, cell(1)), Equal, cell(2))
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        use crate::tokenizer::tokenize;
+        use Token::{CellRef, Greater, NotEqual, Plus, Star};
+
+        let parse = |source: &str| {
+            let mut tokens = tokenize(source).unwrap().into_iter().map(Ok);
+            Parser::new(&mut tokens).ast().unwrap()
+        };
+        assert_eq!(
+            parse("a1 > b1"),
+            Expr::binary(
+                Expr::literal(CellRef((0, 0).into())),
+                Greater,
+                Expr::literal(CellRef((0, 1).into()))
+            )
+        );
+        let one = || Expr::literal(Token::Number(Number::from(1)));
+        assert_eq!(
+            parse("a1 + 1 <> b1 * 1"),
+            Expr::binary(
+                Expr::binary(Expr::literal(CellRef((0, 0).into())), Plus, one()),
+                NotEqual,
+                Expr::binary(Expr::literal(CellRef((0, 1).into())), Star, one())
+            )
+        );
+        for (source, operator) in [
+            ("1<1", Token::Less),
+            ("1<=1", Token::LessEqual),
+            ("1>=1", Token::GreaterEqual),
+            ("1=1", Token::Equal),
+        ] {
+            assert_eq!(
+                parse(source),
+                Expr::binary(one(), operator, one()),
+                "{source}"
+            );
+        }
+    }
+
     #[test]
     fn test_sum_of_sums() {
         use Token::{CellRange, CellRef, Comma, LeftParen, RightParen, Sum};
@@ -228,11 +377,11 @@ mod tests {
         let tokens = vec![
             Sum,
             LeftParen,
-            CellRef((0, 0)),
+            CellRef((0, 0).into()),
             Comma,
             Sum,
             LeftParen,
-            CellRange((Range { start: 0, end: 5 }, Range { start: 1, end: 3 })),
+            CellRange((Range { start: 0, end: 5 }, Range { start: 1, end: 3 }).into()),
             RightParen,
             RightParen,
         ]; // =sum(a1, sum(a1:e2))
@@ -246,16 +395,173 @@ mod tests {
             Expr::call(
                 Expr::literal(Sum),
                 vec![
-                    Expr::literal(CellRef((0, 0)),),
+                    Expr::literal(CellRef((0, 0).into()),),
                     Expr::call(
                         Expr::literal(Sum),
-                        vec![Expr::literal(CellRange((
-                            Range { start: 0, end: 5 },
-                            Range { start: 1, end: 3 }
-                        )),)]
+                        vec![Expr::literal(CellRange(
+                            (Range { start: 0, end: 5 }, Range { start: 1, end: 3 }).into()
+                        ),)]
                     )
                 ]
             )
         );
     }
+
+    #[test]
+    fn test_postfix_percent() {
+        use crate::{
+            eval::{EvalContext, Evaluate},
+            tokenizer::tokenize,
+            value::Value,
+        };
+
+        let parse = |source: &str| {
+            let mut tokens = tokenize(source)?.into_iter().map(Ok);
+            Parser::new(&mut tokens).ast()
+        };
+        let expr = parse("(a1+b1)%").unwrap();
+        assert_eq!(
+            expr,
+            Expr::unary(
+                Token::Percent,
+                Expr::grouping(Expr::binary(
+                    Expr::literal(Token::CellRef((0, 0).into())),
+                    Token::Plus,
+                    Expr::literal(Token::CellRef((0, 1).into())),
+                ))
+            )
+        );
+        assert_eq!(alloc::format!("{expr}"), "(a1 + b1)%");
+        let res = expr.evaluate(&EvalContext::default(), &mut |_, col| {
+            Ok(Value::Number(Number::from(col as u32 * 20 + 5)))
+        });
+        assert_eq!(
+            res[0].clone().unwrap(),
+            Value::Number(Number::from_str("0.3").unwrap())
+        );
+
+        assert_eq!(
+            parse("-a1% * 2").unwrap(),
+            Expr::binary(
+                Expr::unary(
+                    Token::Minus,
+                    Expr::unary(Token::Percent, Expr::literal(Token::CellRef((0, 0).into())))
+                ),
+                Token::Star,
+                Expr::literal(Token::Number(Number::from(2))),
+            )
+        );
+
+        // `%` is never a binary modulo operator.
+        assert!(matches!(
+            parse("a1 % b1"),
+            Err(TableError::ErrorConstructingAst(_))
+        ));
+    }
+
+    #[test]
+    fn test_parentheses() {
+        use crate::{
+            eval::{EvalContext, Evaluate},
+            tokenizer::tokenize,
+            value::Value,
+        };
+
+        let parse = |source: &str| {
+            let mut tokens = tokenize(source)?.into_iter().map(Ok);
+            Parser::new(&mut tokens).ast()
+        };
+        let expr = parse("(5)").unwrap();
+        assert_eq!(
+            expr,
+            Expr::grouping(Expr::literal(Token::Number(Number::from(5))))
+        );
+        let res = expr.evaluate(&EvalContext::default(), &mut |_, _| unreachable!());
+        assert_eq!(res[0].clone().unwrap(), Value::Number(Number::from(5)));
+
+        for source in ["()", "1 + ()", "sum(())"] {
+            assert!(matches!(
+                parse(source),
+                Err(TableError::ErrorConstructingAst(e)) if e == "empty parentheses"
+            ));
+        }
+    }
+
+    #[test]
+    fn test_power_is_right_associative() {
+        use crate::{
+            eval::{EvalContext, Evaluate},
+            tokenizer::tokenize,
+            value::Value,
+        };
+
+        let evaluate = |source: &str| {
+            let mut tokens = tokenize(source).unwrap().into_iter().map(Ok);
+            let expr = Parser::new(&mut tokens).ast().unwrap();
+            let res = expr.evaluate(&EvalContext::default(), &mut |_, _| unreachable!());
+            res[0].clone().unwrap()
+        };
+        for (source, expected) in [
+            ("2^3^2", 512),
+            ("(2^3)^2", 64),
+            ("2*3^2", 18),
+            ("2^3*2", 16),
+            ("-2^2", 4),
+            ("10 - 2^2^0", 8),
+        ] {
+            assert_eq!(
+                evaluate(source),
+                Value::Number(Number::from(expected)),
+                "{source}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_power_tree_shape() {
+        use crate::tokenizer::tokenize;
+        use Token::Caret;
+
+        let parse = |source: &str| {
+            let mut tokens = tokenize(source).unwrap().into_iter().map(Ok);
+            Parser::new(&mut tokens).ast().unwrap()
+        };
+        let number = |n: u32| Expr::literal(Token::Number(Number::from(n)));
+        assert_eq!(
+            parse("2^3^2"),
+            Expr::binary(number(2), Caret, Expr::binary(number(3), Caret, number(2)))
+        );
+        assert_eq!(
+            parse("2*3^2"),
+            Expr::binary(
+                number(2),
+                Token::Star,
+                Expr::binary(number(3), Caret, number(2))
+            )
+        );
+        assert_eq!(
+            parse("-2^2"),
+            Expr::binary(
+                Expr::literal(Token::Number(Number::from(-2))),
+                Caret,
+                number(2)
+            )
+        );
+        assert_eq!(
+            parse("-a1^2"),
+            Expr::binary(
+                Expr::unary(Token::Minus, Expr::literal(Token::CellRef((0, 0).into()))),
+                Caret,
+                number(2)
+            )
+        );
+        assert_eq!(
+            parse("a1%^2"),
+            Expr::binary(
+                Expr::unary(Token::Percent, Expr::literal(Token::CellRef((0, 0).into()))),
+                Caret,
+                number(2)
+            )
+        );
+    }
 }
@@ -0,0 +1,165 @@
+use crate::number::Number;
+
+use crate::{
+    ast::Expr,
+    cell::{Cell, CellKind},
+    error::{TableError, TableResult},
+    eval::{EvalContext, Evaluate},
+    table::Table,
+    value::Value,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Count,
+}
+
+#[derive(Debug, Clone)]
+struct StreamingFormula {
+    aggregate: Aggregate,
+    expr: Expr,
+    value: Number,
+}
+
+/// Evaluates aggregate formulas over rows that arrive one at a time.
+///
+/// Only the row currently being pushed is held in memory, so formulas may
+/// only reference row 1 (e.g. `=b1*c1`); the aggregate accumulates the
+/// value of that expression across every row pushed so far.
+#[derive(Debug, Clone)]
+pub struct StreamingTable {
+    cols: usize,
+    rows: usize,
+    formulas: Vec<StreamingFormula>,
+}
+
+impl StreamingTable {
+    pub fn new(cols: usize) -> Self {
+        Self {
+            cols,
+            rows: 0,
+            formulas: Vec::new(),
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Registers a running aggregate and returns its index for `value`.
+    pub fn add_formula(&mut self, aggregate: Aggregate, source: &str) -> TableResult<usize> {
+        let expr = match Cell::new_expr(source)?.kind {
+            CellKind::Expr { expr, .. } => expr,
+            _ => Err(TableError::InvalidCell(format!(
+                "Streaming formula must start with '=': {source}"
+            )))?,
+        };
+        self.formulas.push(StreamingFormula {
+            aggregate,
+            expr,
+            value: Number::from(0),
+        });
+        Ok(self.formulas.len() - 1)
+    }
+
+    pub fn value(&self, formula: usize) -> Option<&Number> {
+        self.formulas.get(formula).map(|f| &f.value)
+    }
+
+    /// Evaluates every formula against `row` and folds the results into the
+    /// running aggregates. Empty results add nothing to a `Sum`; if a `Sum`
+    /// formula fails no aggregate is updated.
+    pub fn push_row(&mut self, row: &str) -> TableResult<()> {
+        let mut table = Table::new_interpet(row)?;
+        if table.rows() != 1 {
+            return Err(TableError::runtime_error("Expected a single streamed row"));
+        }
+        if table.cols() != self.cols {
+            return Err(TableError::MismatchedColumns);
+        }
+
+        let cols = self.cols;
+        let context = EvalContext::default();
+        let mut results = Vec::with_capacity(self.formulas.len());
+        for formula in self.formulas.iter() {
+            let res = formula.expr.evaluate(&context, &mut |row, col| {
+                if row != 0 || col >= cols {
+                    return Err(TableError::runtime_error(
+                        "Streaming formulas can only reference the current row",
+                    ));
+                }
+                table.evaluate_cell(row, col)
+            });
+            let res = match res.len() {
+                1 => res[0].clone(),
+                _ => Err(TableError::MultipleCellReturn),
+            };
+            match formula.aggregate {
+                Aggregate::Sum => results.push(match res? {
+                    Value::Empty => Ok(Number::from(0)),
+                    value => Ok(value.as_number()?),
+                }),
+                Aggregate::Count => results.push(match res {
+                    Ok(Value::Empty) => Err(TableError::EmptyCellEvaluation),
+                    res => res.map(|_| Number::from(1)),
+                }),
+            }
+        }
+
+        for (formula, res) in self.formulas.iter_mut().zip(results) {
+            match (formula.aggregate, res) {
+                (Aggregate::Sum, Ok(d)) => formula.value += d,
+                (Aggregate::Count, Ok(_)) => formula.value += Number::from(1),
+                _ => {}
+            }
+        }
+        self.rows += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_running_sum_and_count() {
+        let mut stream = StreamingTable::new(3);
+        let sum = stream.add_formula(Aggregate::Sum, "=b1*c1").unwrap();
+        let count = stream.add_formula(Aggregate::Count, "=a1").unwrap();
+
+        stream.push_row("1|2|3").unwrap();
+        assert_eq!(stream.value(sum), Some(&Number::from(6)));
+        assert_eq!(stream.value(count), Some(&Number::from(1)));
+
+        stream.push_row("|4|=b1+1").unwrap();
+        assert_eq!(stream.value(sum), Some(&Number::from(26)));
+        assert_eq!(stream.value(count), Some(&Number::from(1)));
+
+        stream.push_row("7|1|1").unwrap();
+        assert_eq!(stream.value(sum), Some(&Number::from(27)));
+        assert_eq!(stream.value(count), Some(&Number::from(2)));
+        assert_eq!(stream.rows(), 3);
+    }
+
+    #[test]
+    fn test_sum_skips_empty_cells() {
+        let mut stream = StreamingTable::new(2);
+        let sum = stream.add_formula(Aggregate::Sum, "=b1").unwrap();
+        stream.push_row("1|").unwrap();
+        stream.push_row("1|4").unwrap();
+        assert_eq!(stream.value(sum), Some(&Number::from(4)));
+        assert_eq!(stream.rows(), 2);
+    }
+
+    #[test]
+    fn test_rejects_rows_outside_stream() {
+        let mut stream = StreamingTable::new(2);
+        let sum = stream.add_formula(Aggregate::Sum, "=a2").unwrap();
+        assert!(stream.push_row("1|2").is_err());
+        assert!(stream.push_row("1|2|3").is_err());
+        assert_eq!(stream.value(sum), Some(&Number::from(0)));
+        assert_eq!(stream.rows(), 0);
+    }
+}
@@ -1,75 +1,998 @@
-use std::collections::HashSet;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::Write,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use bigdecimal::BigDecimal;
+use bigdecimal::{RoundingMode, ToPrimitive};
 
 use crate::{
     ast::Expr,
-    cell::{Cell, CellKind},
+    cell::{Cell, CellFormat, CellKind, ParseOptions},
     error::{TableError, TableResult},
-    eval::Evaluate,
+    eval::{EvalContext, Evaluate, PrecisionBudget},
     grid::Grid,
+    number::Number,
+    tokenizer::cell_name,
+    value::Value,
 };
 
+/// Statistics collected by `Table::run_with_report`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunReport {
+    /// Formula evaluations performed, including repeated evaluations of a cell.
+    pub evaluations: usize,
+    /// How many times each formula cell was evaluated.
+    pub evaluations_per_cell: HashMap<(usize, usize), usize>,
+    /// Cells parsed on first access, in tables built with `Table::new_interpet_lazy`.
+    pub lazy_parses: usize,
+}
+
+/// How the most recent evaluation of a formula cell went, recorded while
+/// profiling is enabled with `Table::set_profiling`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellProfile {
+    /// Time spent evaluating the formula, including the cells it referenced.
+    pub duration: Duration,
+    /// Cell values the formula asked for, one per reference or range cell.
+    pub sub_evaluations: usize,
+}
+
+fn empty_cell<'source, T: Evaluate>() -> Cell<'source, T> {
+    Cell {
+        source: "".into(),
+        kind: CellKind::Empty,
+        format: None,
+    }
+}
+
+const NO_LIMITS: (usize, usize) = (usize::MAX, usize::MAX);
+
+/// Problems found by `Table::new_interpet_lenient`, each with the cell it concerns.
+pub type CellErrors = Vec<((usize, usize), TableError)>;
+
+type CellParser<T> = fn(&str, &ParseOptions) -> TableResult<CellKind<T>>;
+
 #[derive(Debug, Clone)]
 pub struct Table<'source, T>
 where
     T: Evaluate,
 {
     grid: Grid<TableResult<Cell<'source, T>>>,
+    spill: bool,
+    context: Arc<EvalContext>,
+    options: ParseOptions,
+    report: RunReport,
+    /// Parses the `Unparsed` cells of a lazily built table.
+    lazy_parser: Option<CellParser<T>>,
+    /// Values supplied by `set_override`, used in place of the cells' contents.
+    overrides: HashMap<(usize, usize), Value>,
+    profiling: bool,
+    profiles: HashMap<(usize, usize), CellProfile>,
 }
 
 impl<'source> Table<'source, Expr> {
     pub fn new_interpet(source: &'source str) -> TableResult<Self> {
-        let mut cells = Vec::new();
-        let mut rows = 0;
+        Self::new_interpet_with_delimiter(source, '|')
+    }
+
+    /// Parses `source` like `new_interpet` with cells separated by `delimiter`,
+    /// e.g. `'\t'` for tab-separated input. `\` followed by the delimiter is a
+    /// literal delimiter inside a cell.
+    pub fn new_interpet_with_delimiter(source: &'source str, delimiter: char) -> TableResult<Self> {
+        Self::from_source(
+            source,
+            ParseOptions::default(),
+            NO_LIMITS,
+            delimiter,
+            Cell::new_expr_with_options,
+        )
+    }
 
-        let mut previous_cols = None;
-        for row in source.lines() {
-            let mut current_cols = 0;
-            for col in row.split('|') {
-                let cell = Cell::new_expr(col);
+    /// Parses `source` like `new_interpet`, interpreting each cell with `options`.
+    /// The options are kept for later `set_cell` calls.
+    pub fn new_interpet_with_options(
+        source: &'source str,
+        options: ParseOptions,
+    ) -> TableResult<Self> {
+        Self::from_source(source, options, NO_LIMITS, '|', Cell::new_expr_with_options)
+    }
+
+    /// Parses `source` like `new_interpet`, failing with "table too large" as
+    /// soon as it has more than `max_rows` rows or `max_cols` columns, before
+    /// the cells beyond the limits are parsed. For untrusted input.
+    pub fn new_interpet_with_limits(
+        source: &'source str,
+        max_rows: usize,
+        max_cols: usize,
+    ) -> TableResult<Self> {
+        Self::from_source(
+            source,
+            ParseOptions::default(),
+            (max_rows, max_cols),
+            '|',
+            Cell::new_expr_with_options,
+        )
+    }
+
+    /// Splits `source` like `new_interpet` but leaves each cell unparsed until
+    /// it is first evaluated, for large tables where only a few cells are read.
+    pub fn new_interpet_lazy(source: &'source str) -> TableResult<Self> {
+        let table = Self::from_source(
+            source,
+            ParseOptions::default(),
+            NO_LIMITS,
+            '|',
+            |source, _| {
+                let (format, source) = CellFormat::split_directives(source);
+                let kind = if source.is_empty() {
+                    CellKind::Empty
+                } else {
+                    CellKind::Unparsed
+                };
+                Ok(Cell {
+                    source,
+                    kind,
+                    format,
+                })
+            },
+        )?;
+        Ok(Self {
+            lazy_parser: Some(|source, options| {
+                Cell::new_expr_with_options(source, options).map(|cell| cell.kind)
+            }),
+            ..table
+        })
+    }
+
+    /// Parses `source` like `new_interpet`, spreading the cells over the rayon
+    /// thread pool. The resulting table is the same as `new_interpet`'s.
+    #[cfg(feature = "parallel")]
+    pub fn new_interpet_parallel(source: &'source str) -> TableResult<Self> {
+        use rayon::prelude::*;
+
+        let options = ParseOptions::default();
+        let fields = split_source(source, NO_LIMITS, '|')?;
+        let cells = fields
+            .fields
+            .into_par_iter()
+            .map(|field| Cell::new_expr_with_options(field, &options))
+            .collect();
+        Ok(Self {
+            options,
+            ..Self::from_grid(Grid::new(fields.rows, fields.cols, cells))
+        })
+    }
+
+    /// Parses `source` like `new_interpet` but never fails, for editors showing
+    /// every problem at once. Cells that fail to parse are kept as errors in the
+    /// grid, and rows shorter than the widest are padded with empty cells.
+    /// Returns the table with each problem and the cell it concerns; a short
+    /// row is reported at its first padded cell.
+    pub fn new_interpet_lenient(source: &'source str) -> (Self, CellErrors) {
+        let source_rows = source
+            .lines()
+            .map(|row| split_row(row, '|'))
+            .collect::<Vec<_>>();
+        let rows = source_rows.len();
+        let cols = source_rows.iter().map(Vec::len).max().unwrap_or(0);
+        let options = ParseOptions::default();
+        let mut errors = Vec::new();
+        if rows == 0 {
+            errors.push(((0, 0), TableError::EmptyTable));
+        }
+        let mut cells = Vec::with_capacity(rows * cols);
+        for (row, fields) in source_rows.into_iter().enumerate() {
+            let width = fields.len();
+            for (col, field) in fields.into_iter().enumerate() {
+                let cell = Cell::new_expr_with_options(field, &options);
+                if let Err(e) = &cell {
+                    errors.push(((row, col), e.clone()));
+                }
                 cells.push(cell);
-                current_cols += 1;
             }
-            match (previous_cols, current_cols) {
-                (None, c) => previous_cols = Some(c),
-                (Some(p), c) if p != c => Err(TableError::MismatchedColumns)?,
-                _ => {}
+            if width < cols {
+                errors.push(((row, width), TableError::MismatchedColumns));
+                cells.extend((width..cols).map(|_| Ok(empty_cell())));
+            }
+        }
+        let table = Self {
+            options,
+            ..Self::from_grid(Grid::new(rows, cols, cells))
+        };
+        (table, errors)
+    }
+
+    fn from_source<F>(
+        source: &'source str,
+        options: ParseOptions,
+        limits: (usize, usize),
+        delimiter: char,
+        mut new_cell: F,
+    ) -> TableResult<Self>
+    where
+        F: FnMut(Cow<'source, str>, &ParseOptions) -> TableResult<Cell<'source, Expr>>,
+    {
+        let fields = split_source(source, limits, delimiter)?;
+        let cells = fields
+            .fields
+            .into_iter()
+            .map(|field| new_cell(field, &options))
+            .collect();
+        Ok(Self {
+            options,
+            ..Self::from_grid(Grid::new(fields.rows, fields.cols, cells))
+        })
+    }
+}
+
+impl<'source> Table<'source, Expr> {
+    /// A `rows` x `cols` table of empty cells, to be filled with `set_cell`.
+    pub fn empty(rows: usize, cols: usize) -> Self {
+        let cells = (0..rows * cols)
+            .map(|_| {
+                Ok(Cell {
+                    source: "".into(),
+                    kind: CellKind::Empty,
+                    format: None,
+                })
+            })
+            .collect();
+        Self::from_grid(Grid::new(rows, cols, cells))
+    }
+
+    /// Replaces the cell at `(row, col)` with `source`, parsed as in `new_interpet`.
+    ///
+    /// A parse error is stored in the cell and returned. The cached results of
+    /// the formulas depending on the cell are cleared, so the next `run`
+    /// recalculates only those.
+    pub fn set_cell(&mut self, row: usize, col: usize, source: &'source str) -> TableResult<()> {
+        self.check_in_bounds(row, col)?;
+        let cell = Cell::new_expr_with_options(source, &self.options);
+        self.grid[(row, col)] = cell.clone();
+        self.invalidate_dependents(row, col);
+        cell.map(|_| ())
+    }
+
+    /// Drops the cached results of the formula at `(row, col)` and of every formula
+    /// depending on it, directly or through other formulas. Formulas reading a
+    /// `@label` are always dropped, as are all results while spilling is enabled,
+    /// since either can move what a formula reads.
+    fn invalidate_dependents(&mut self, row: usize, col: usize) {
+        if self.spill {
+            return self.invalidate();
+        }
+        let mut stale = HashSet::from([(row, col)]);
+        let mut dependents = HashMap::<_, Vec<_>>::new();
+        for other_row in 0..self.grid.rows {
+            for other_col in 0..self.grid.cols {
+                let coord = (other_row, other_col);
+                if let Ok(Cell {
+                    kind: CellKind::Expr { expr, .. },
+                    ..
+                }) = &self.grid[coord]
+                {
+                    if expr.has_labels() {
+                        stale.insert(coord);
+                    }
+                }
+                for dependency in self.dependencies_of(other_row, other_col) {
+                    dependents.entry(dependency).or_default().push(coord);
+                }
+            }
+        }
+        let mut queue = stale.iter().copied().collect::<Vec<_>>();
+        while let Some(coord) = queue.pop() {
+            for &dependent in dependents.get(&coord).into_iter().flatten() {
+                if stale.insert(dependent) {
+                    queue.push(dependent);
+                }
+            }
+        }
+        for coord in stale {
+            if let Ok(Cell {
+                kind: CellKind::Expr { result, .. },
+                ..
+            }) = &mut self.grid[coord]
+            {
+                *result = None;
             }
+        }
+    }
 
-            rows += 1;
+    /// Sets every cell in column `col` to `template` with `{r}` replaced by the
+    /// cell's one-based row number, so `=a{r}*2` fills down as `=a1*2`, `=a2*2`, ...
+    ///
+    /// Every row is set even if some fail to parse; the first parse error is returned.
+    pub fn apply_formula_to_column(&mut self, col: usize, template: &str) -> TableResult<()> {
+        self.check_in_bounds(0, col)?;
+        let mut first_error = Ok(());
+        for row in 0..self.grid.rows {
+            let source = template.replace("{r}", &(row + 1).to_string());
+            let cell = Cell::new_expr_with_options(source, &self.options);
+            if let Err(e) = &cell {
+                first_error = first_error.and(Err(e.clone()));
+            }
+            self.grid[(row, col)] = cell;
         }
-        match previous_cols {
-            None => Err(TableError::EmptyTable),
-            Some(cols) => Ok(Self {
-                grid: Grid::new(rows, cols, cells),
-            }),
+        self.invalidate();
+        first_error
+    }
+
+    /// Inserts an empty row before row `at` (or at the bottom), moving the rows
+    /// below down. Formula references and labels follow the cells they point
+    /// at, and ranges spanning the new row grow to include it.
+    pub fn insert_row(&mut self, at: usize) {
+        self.grid.insert_row(at, Ok(empty_cell()));
+        self.shift_references(|(row, col)| (if row >= at { row + 1 } else { row }, col));
+    }
+
+    /// Inserts an empty column before column `at`, as `insert_row` does for rows.
+    pub fn insert_column(&mut self, at: usize) {
+        self.grid.insert_col(at, Ok(empty_cell()));
+        self.shift_references(|(row, col)| (row, if col >= at { col + 1 } else { col }));
+    }
+
+    /// Moves every formula reference and label with `shift` after an insertion.
+    /// Formulas that change are written back to their source, keeping the prefix.
+    fn shift_references<F>(&mut self, shift: F)
+    where
+        F: Fn((usize, usize)) -> (usize, usize),
+    {
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                let _ = self.parse_deferred(row, col);
+                if let Ok(Cell {
+                    kind: CellKind::Expr { expr, .. },
+                    source,
+                    ..
+                }) = &mut self.grid[(row, col)]
+                {
+                    let before = expr.clone();
+                    expr.shift_references(&shift);
+                    if *expr != before {
+                        let prefix = source.chars().next().filter(|c| *c != '-');
+                        *source = prefix.into_iter().chain(expr.to_string().chars()).collect();
+                    }
+                }
+            }
+        }
+        let context = Arc::make_mut(&mut self.context);
+        for coord in context.labels.values_mut() {
+            *coord = shift(*coord);
+        }
+        if let Some(bounds) = &mut context.range_bounds {
+            *bounds = (self.grid.rows, self.grid.cols);
+        }
+        self.overrides = std::mem::take(&mut self.overrides)
+            .into_iter()
+            .map(|(coord, value)| (shift(coord), value))
+            .collect();
+        self.profiles.clear();
+        self.invalidate();
+    }
+
+    /// Builds a table of `Number` cells directly from precomputed values.
+    pub fn from_grid_values(grid: Grid<Number>) -> Self {
+        Self::from_grid(grid.map(|d| {
+            Ok(Cell {
+                source: "".into(),
+                kind: CellKind::Number(d),
+                format: None,
+            })
+        }))
+    }
+}
+
+impl<'source> Table<'source, Expr> {
+    /// Evaluates the cell at `(row, col)` and describes how its value was reached,
+    /// one indented `expression = value` line per sub-expression.
+    pub fn explain(&mut self, row: usize, col: usize) -> String {
+        let name = cell_name(row, col);
+        if let Err(e) = self.check_in_bounds(row, col) {
+            return format!("{name} = {e}");
+        }
+        let _ = self.parse_deferred(row, col);
+        let cell = match self.grid[(row, col)].clone() {
+            Ok(cell) => cell,
+            Err(e) => return format!("{name} = {e}"),
+        };
+        let mut steps = Vec::new();
+        if let CellKind::Expr { expr, .. } = &cell.kind {
+            let context = self.context.clone();
+            expr.explain(
+                &context,
+                &mut |r, c| self.evaluate_cell(r, c),
+                0,
+                &mut steps,
+            );
         }
+        let value = match self.evaluate_cell(row, col) {
+            Ok(d) => d.to_string(),
+            Err(e) => e.to_string(),
+        };
+        let mut lines = vec![match &cell.kind {
+            CellKind::Expr { expr, .. } => format!("{name} = {expr} = {value}"),
+            _ => format!("{name} = {value}"),
+        }];
+        lines.extend(steps.into_iter().skip(1));
+        lines.join("\n")
     }
 }
 
 impl<'source, T: Evaluate> Table<'source, T> {
-    pub fn evaluate_cell(
+    fn from_grid(grid: Grid<TableResult<Cell<'source, T>>>) -> Self {
+        Self {
+            grid,
+            spill: false,
+            context: Arc::new(EvalContext::default()),
+            options: ParseOptions::default(),
+            report: RunReport::default(),
+            lazy_parser: None,
+            overrides: HashMap::new(),
+            profiling: false,
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Stacks tables with matching column counts on top of each other.
+    ///
+    /// Cell references are kept as written, so formulas in later tables still
+    /// point at rows counted from the top of the combined table.
+    pub fn concat_vertical(tables: &[Self]) -> TableResult<Self> {
+        let cols = tables.first().ok_or(TableError::EmptyTable)?.grid.cols;
+        let mut cells = Vec::new();
+        let mut rows = 0;
+        for table in tables {
+            if table.grid.cols != cols {
+                return Err(TableError::MismatchedColumns);
+            }
+            for row in 0..table.grid.rows {
+                for col in 0..cols {
+                    cells.push(table.grid[(row, col)].clone());
+                }
+            }
+            rows += table.grid.rows;
+        }
+        Ok(Self {
+            lazy_parser: tables.iter().find_map(|t| t.lazy_parser),
+            ..Self::from_grid(Grid::new(rows, cols, cells))
+        })
+    }
+
+    /// Places tables with matching row counts side by side.
+    ///
+    /// As with `concat_vertical`, cell references are not adjusted.
+    pub fn concat_horizontal(tables: &[Self]) -> TableResult<Self> {
+        let rows = tables.first().ok_or(TableError::EmptyTable)?.grid.rows;
+        if tables.iter().any(|t| t.grid.rows != rows) {
+            return Err(TableError::MismatchedRows);
+        }
+        let cols = tables.iter().map(|t| t.grid.cols).sum();
+        let mut cells = Vec::new();
+        for row in 0..rows {
+            for table in tables {
+                for col in 0..table.grid.cols {
+                    cells.push(table.grid[(row, col)].clone());
+                }
+            }
+        }
+        Ok(Self {
+            lazy_parser: tables.iter().find_map(|t| t.lazy_parser),
+            ..Self::from_grid(Grid::new(rows, cols, cells))
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.grid.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.grid.cols
+    }
+
+    /// `CellOutOfBounds` unless `(row, col)` is inside the table.
+    fn check_in_bounds(&self, row: usize, col: usize) -> TableResult<()> {
+        match self.grid.get(row, col) {
+            Some(_) => Ok(()),
+            None => Err(TableError::CellOutOfBounds((row, col))),
+        }
+    }
+
+    /// `CellOutOfBounds` for the last cell of the `(rows, cols)` range unless the
+    /// whole range is inside the table. An empty range is always in bounds.
+    fn check_range_in_bounds(
+        &self,
+        (rows, cols): &(Range<usize>, Range<usize>),
+    ) -> TableResult<()> {
+        if rows.is_empty() || cols.is_empty() {
+            return Ok(());
+        }
+        self.check_in_bounds(rows.end - 1, cols.end - 1)
+    }
+
+    /// The typed value of the cell at `(row, col)` without evaluating anything:
+    /// the stored value for literal cells and the cached result for formulas.
+    /// Formulas that have not been evaluated yet (see `run`) are an error.
+    /// Overridden cells report their override.
+    pub fn get_value(&self, row: usize, col: usize) -> TableResult<Value> {
+        self.check_in_bounds(row, col)?;
+        if let Some(value) = self.overrides.get(&(row, col)) {
+            return Ok(value.clone());
+        }
+        let cell = self.grid[(row, col)].as_ref().map_err(Clone::clone)?;
+        let parsed;
+        let kind = match &cell.kind {
+            CellKind::Unparsed => {
+                parsed = self.parse_deferred_source(&cell.source)?;
+                &parsed
+            }
+            kind => kind,
+        };
+        match kind {
+            CellKind::Empty => Ok(Value::Empty),
+            CellKind::Number(d) => Ok(Value::Number(d.clone())),
+            CellKind::Text(s) => Ok(Value::Text(s.clone())),
+            CellKind::Bool(b) => Ok(Value::Bool(*b)),
+            CellKind::Spilled { result, .. } => result.clone(),
+            CellKind::Expr { result: None, .. } | CellKind::Unparsed => {
+                Err(TableError::runtime_error(format!(
+                    "Cell {} has not been evaluated",
+                    cell_name(row, col)
+                )))
+            }
+            CellKind::Expr {
+                result: Some(result),
+                ..
+            } => result.clone(),
+        }
+    }
+
+    /// The cell at `(row, col)` as a number, read like `get_value`: the stored
+    /// number or the cached formula result, with booleans as 1 or 0. Text is an
+    /// error, as are empty cells (`EmptyCellEvaluation`).
+    pub fn get_number(&self, row: usize, col: usize) -> TableResult<Number> {
+        self.get_value(row, col)?.as_number()
+    }
+
+    /// The evaluated table as a dense row-major matrix of `f64`, for handing to
+    /// numerical code. Every cell must be a number or an evaluated formula giving
+    /// one (see `run`); empty cells are an error unless `empty_as_zero` is set.
+    pub fn to_matrix(&self, empty_as_zero: bool) -> TableResult<Vec<Vec<f64>>> {
+        (0..self.grid.rows)
+            .map(|row| {
+                (0..self.grid.cols)
+                    .map(|col| match self.get_value(row, col) {
+                        Ok(Value::Number(d)) => d.to_f64().ok_or_else(|| {
+                            TableError::runtime_error(format!(
+                                "Cell {} does not fit in an f64",
+                                cell_name(row, col)
+                            ))
+                        }),
+                        Ok(Value::Empty) if empty_as_zero => Ok(0.0),
+                        Ok(Value::Empty) => Err(TableError::EmptyCellEvaluation),
+                        Ok(_) => Err(TableError::runtime_error(format!(
+                            "Cell {} is not a number",
+                            cell_name(row, col)
+                        ))),
+                        Err(e) => Err(e),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Whether the cell at `(row, col)` holds a formula, without evaluating it.
+    /// Cells outside the table or that failed to parse are not formulas.
+    pub fn is_formula(&self, row: usize, col: usize) -> bool {
+        self.cell_kind_is(row, col, |kind| matches!(kind, CellKind::Expr { .. }))
+    }
+
+    /// Whether the cell at `(row, col)` holds a number literal, as for `is_formula`.
+    pub fn is_number(&self, row: usize, col: usize) -> bool {
+        self.cell_kind_is(row, col, |kind| matches!(kind, CellKind::Number(_)))
+    }
+
+    /// Whether the cell at `(row, col)` was left empty, as for `is_formula`.
+    /// Cells holding values spilled from a formula above are not empty.
+    pub fn is_empty(&self, row: usize, col: usize) -> bool {
+        self.cell_kind_is(row, col, |kind| matches!(kind, CellKind::Empty))
+    }
+
+    fn cell_kind_is<P>(&self, row: usize, col: usize, predicate: P) -> bool
+    where
+        P: Fn(&CellKind<T>) -> bool,
+    {
+        if row >= self.grid.rows || col >= self.grid.cols {
+            return false;
+        }
+        match &self.grid[(row, col)] {
+            Ok(Cell {
+                kind: CellKind::Unparsed,
+                source,
+                ..
+            }) => self
+                .parse_deferred_source(source)
+                .is_ok_and(|kind| predicate(&kind)),
+            Ok(cell) => predicate(&cell.kind),
+            Err(_) => false,
+        }
+    }
+
+    /// Cells referenced directly by the formula at `(row, col)`.
+    pub fn dependencies_of(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        self.formula_references(row, col).unwrap_or_default()
+    }
+
+    /// The references of the formula at `(row, col)`, or `None` if it is not a
+    /// formula or lies outside the table.
+    fn formula_references(&self, row: usize, col: usize) -> Option<Vec<(usize, usize)>> {
+        match self.grid.get(row, col) {
+            Some(Ok(Cell {
+                kind: CellKind::Expr { expr, .. },
+                ..
+            })) => Some(expr.references()),
+            Some(Ok(Cell {
+                kind: CellKind::Unparsed,
+                source,
+                ..
+            })) => match self.parse_deferred_source(source) {
+                Ok(CellKind::Expr { expr, .. }) => Some(expr.references()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// A JSON object mapping the A1 name of every formula cell to the cells it
+    /// references directly, e.g. `{"c1":["a1","b1"]}`, in row order.
+    pub fn dependencies_json(&self) -> String {
+        let mut entries = Vec::new();
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                if let Some(references) = self.formula_references(row, col) {
+                    let references = references
+                        .into_iter()
+                        .map(|(r, c)| format!("\"{}\"", cell_name(r, c)))
+                        .collect::<Vec<_>>();
+                    entries.push(format!(
+                        "\"{}\":[{}]",
+                        cell_name(row, col),
+                        references.join(",")
+                    ));
+                }
+            }
+        }
+        format!("{{{}}}", entries.join(","))
+    }
+
+    /// Cells whose formulas reference `(row, col)` directly.
+    pub fn dependents_of(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
+        let mut dependents = Vec::new();
+        for other_row in 0..self.grid.rows {
+            for other_col in 0..self.grid.cols {
+                if self
+                    .dependencies_of(other_row, other_col)
+                    .contains(&(row, col))
+                {
+                    dependents.push((other_row, other_col));
+                }
+            }
+        }
+        dependents
+    }
+
+    /// Non-empty cells that no formula references, in row order, to find dead
+    /// data. A sheet's results are never referenced either, so cells listed in
+    /// `outputs` are left out, as are labelled cells.
+    pub fn unused_cells(&self, outputs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut used = outputs.iter().copied().collect::<HashSet<_>>();
+        used.extend(self.context.labels.values().copied());
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                used.extend(self.dependencies_of(row, col));
+            }
+        }
+        let mut unused = Vec::new();
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                let content = !matches!(
+                    self.grid[(row, col)],
+                    Ok(Cell {
+                        kind: CellKind::Empty | CellKind::Spilled { .. },
+                        ..
+                    })
+                );
+                if content && !used.contains(&(row, col)) {
+                    unused.push((row, col));
+                }
+            }
+        }
+        unused
+    }
+
+    /// Checks that no formula depends on itself, directly or through other cells,
+    /// without evaluating anything. The error names a cell on the first cycle found.
+    pub fn validate_acyclic(&self) -> TableResult<()> {
+        self.evaluation_order().map(|_| ())
+    }
+
+    /// Every non-empty cell in an order `run` could evaluate them in, each after
+    /// the cells it references, without evaluating anything. Cells are visited
+    /// column by column like `run`; a cycle is an error as in `validate_acyclic`.
+    pub fn evaluation_order(&self) -> TableResult<Vec<(usize, usize)>> {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Visit {
+            New,
+            InProgress,
+            Done,
+        }
+        let mut visits = Grid::new(
+            self.grid.rows,
+            self.grid.cols,
+            vec![Visit::New; self.grid.rows * self.grid.cols],
+        );
+
+        let mut order = Vec::new();
+        for col in 0..self.grid.cols {
+            for row in 0..self.grid.rows {
+                if visits[(row, col)] != Visit::New {
+                    continue;
+                }
+                visits[(row, col)] = Visit::InProgress;
+                let mut stack = vec![((row, col), self.dependencies_of(row, col), 0)];
+                while let Some((cell, dependencies, next)) = stack.last_mut() {
+                    let Some(&dependency) = dependencies.get(*next) else {
+                        visits[*cell] = Visit::Done;
+                        if !matches!(
+                            self.grid[*cell],
+                            Ok(Cell {
+                                kind: CellKind::Empty | CellKind::Spilled { .. },
+                                ..
+                            })
+                        ) {
+                            order.push(*cell);
+                        }
+                        stack.pop();
+                        continue;
+                    };
+                    *next += 1;
+                    if dependency.0 >= self.grid.rows || dependency.1 >= self.grid.cols {
+                        continue;
+                    }
+                    match visits[dependency] {
+                        Visit::InProgress => {
+                            let path = stack.iter().map(|(cell, ..)| *cell).collect::<Vec<_>>();
+                            return Err(cycle_error(&path, dependency));
+                        }
+                        Visit::Done => {}
+                        Visit::New => {
+                            visits[dependency] = Visit::InProgress;
+                            let dependencies = self.dependencies_of(dependency.0, dependency.1);
+                            stack.push((dependency, dependencies, 0));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(order)
+    }
+
+    /// When enabled, formulas returning several values (e.g. `=a1:a3`) write
+    /// them into the empty cells below instead of failing with `MultipleCellReturn`.
+    pub fn set_spill(&mut self, spill: bool) {
+        self.spill = spill;
+    }
+
+    /// When enabled, every formula evaluation records a `CellProfile`, read back
+    /// with `cell_profile`. Disabling drops the recorded profiles.
+    pub fn set_profiling(&mut self, profiling: bool) {
+        self.profiling = profiling;
+        if !profiling {
+            self.profiles.clear();
+        }
+    }
+
+    /// The profile of the last evaluation of the formula at `(row, col)`, if it
+    /// was evaluated while profiling was enabled.
+    pub fn cell_profile(&self, row: usize, col: usize) -> Option<CellProfile> {
+        self.profiles.get(&(row, col)).copied()
+    }
+
+    /// Clamps every arithmetic result into `[min, max]`, or disables clamping with `None`.
+    pub fn set_clamp(&mut self, clamp: Option<(Number, Number)>) -> TableResult<()> {
+        if let Some((min, max)) = &clamp {
+            if min > max {
+                return Err(TableError::runtime_error(format!(
+                    "Invalid clamp range: {min} is greater than {max}"
+                )));
+            }
+        }
+        Arc::make_mut(&mut self.context).clamp = clamp;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Sets how `round` and scaled divisions round their results (half-up by default).
+    pub fn set_rounding_mode(&mut self, rounding: RoundingMode) {
+        Arc::make_mut(&mut self.context).rounding = rounding;
+        self.invalidate();
+    }
+
+    /// When enabled, text that reads as a number (e.g. `'42`) is used as that
+    /// number in arithmetic; otherwise, the default, it is an error.
+    pub fn set_coerce_text_numbers(&mut self, coerce: bool) {
+        Arc::make_mut(&mut self.context).coerce_text_numbers = coerce;
+        self.invalidate();
+    }
+
+    /// When enabled, ranges reaching past the edge of the table (e.g. `a1:a100`
+    /// in a ten-row table) cover only the cells inside it; otherwise, the
+    /// default, the missing cells are `CellOutOfBounds` errors.
+    pub fn set_clamp_ranges(&mut self, clamp: bool) {
+        Arc::make_mut(&mut self.context).range_bounds =
+            clamp.then_some((self.grid.rows, self.grid.cols));
+        self.invalidate();
+    }
+
+    /// Rounds every division result to `scale` decimal places, or disables it with `None`.
+    pub fn set_division_scale(&mut self, scale: Option<i64>) {
+        Arc::make_mut(&mut self.context).division_scale = scale;
+        self.invalidate();
+    }
+
+    /// Limits the significant digits of multiplication results, or removes the limit with `None`.
+    pub fn set_precision_budget(&mut self, precision: Option<PrecisionBudget>) {
+        Arc::make_mut(&mut self.context).precision = precision;
+        self.invalidate();
+    }
+
+    /// Replaces the variables that names in formulas (e.g. `=price * quantity`)
+    /// resolve to. Names are case-insensitive.
+    pub fn set_variables(&mut self, variables: impl IntoIterator<Item = (String, Number)>) {
+        Arc::make_mut(&mut self.context).variables = variables
+            .into_iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value))
+            .collect();
+        self.invalidate();
+    }
+
+    /// Labels the cell at `(row, col)` so formulas can refer to it as `@name`.
+    /// Labels are case-insensitive and follow the cell when rows are inserted.
+    pub fn label_cell(&mut self, row: usize, col: usize, name: &str) -> TableResult<()> {
+        self.check_in_bounds(row, col)?;
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(TableError::runtime_error(format!(
+                "Invalid label name \"{name}\""
+            )));
+        }
+        Arc::make_mut(&mut self.context)
+            .labels
+            .insert(name.to_ascii_lowercase(), (row, col));
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Treats the cell at `(row, col)` as an external input holding `value`:
+    /// evaluation uses it instead of the cell's contents, so a formula there is
+    /// not evaluated and its dependents see `value`.
+    pub fn set_override(&mut self, row: usize, col: usize, value: Value) -> TableResult<()> {
+        self.check_in_bounds(row, col)?;
+        self.overrides.insert((row, col), value);
+        self.invalidate();
+        Ok(())
+    }
+
+    /// Removes the override of the cell at `(row, col)`, if any, so it is
+    /// evaluated from its contents again.
+    pub fn clear_override(&mut self, row: usize, col: usize) {
+        if self.overrides.remove(&(row, col)).is_some() {
+            self.invalidate();
+        }
+    }
+
+    /// Drops every cached formula result, including values spilled into other cells.
+    fn invalidate(&mut self) {
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                if let Ok(cell) = &mut self.grid[(row, col)] {
+                    match &mut cell.kind {
+                        CellKind::Expr { result, .. } => *result = None,
+                        CellKind::Spilled { .. } => cell.kind = CellKind::Empty,
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluates the cell at `(row, col)`, caching formula results. Reaching a
+    /// cell that is already being evaluated is a `RecursiveCellExpr`.
+    pub fn evaluate_cell(&mut self, row: usize, col: usize) -> TableResult<Value> {
+        self.evaluate_chained(row, col, &mut CallChain::default())
+    }
+
+    /// `evaluate_cell` within `chain`, which is left as it was on return.
+    fn evaluate_chained(
         &mut self,
         row: usize,
         col: usize,
-        mut call_chain: HashSet<(usize, usize)>,
-    ) -> TableResult<BigDecimal> {
-        if !call_chain.insert((row, col)) {
-            return Err(TableError::RecursiveCellExpr((row, col)));
+        chain: &mut CallChain,
+    ) -> TableResult<Value> {
+        chain.within((row, col), |chain| self.evaluate_entered(row, col, chain))
+    }
+
+    /// `evaluate_cell` once `(row, col)` has been added to `chain`.
+    fn evaluate_entered(
+        &mut self,
+        row: usize,
+        col: usize,
+        chain: &mut CallChain,
+    ) -> TableResult<Value> {
+        if let Some(value) = self.overrides.get(&(row, col)).cloned() {
+            if let Ok(Cell {
+                kind: CellKind::Expr { result, .. },
+                ..
+            }) = &mut self.grid[(row, col)]
+            {
+                *result = Some(Ok(value.clone()));
+            }
+            return Ok(value);
         }
 
-        let cell = self.grid[(row, col)].clone()?;
+        let cell = self
+            .grid
+            .get(row, col)
+            .ok_or(TableError::CellOutOfBounds((row, col)))?
+            .clone()?;
         match cell.kind.clone() {
-            CellKind::Empty => Err(TableError::EmptyCellEvaluation),
-            CellKind::Number(d) => Ok(d),
+            CellKind::Unparsed => {
+                self.parse_deferred(row, col)?;
+                self.evaluate_entered(row, col, chain)
+            }
+            CellKind::Empty if self.spill => match self.spill_anchor_above(row, col) {
+                Some(anchor) => {
+                    let _ = self.evaluate_chained(anchor, col, chain);
+                    match self.grid[(row, col)].clone()?.kind {
+                        CellKind::Spilled { result, .. } => result,
+                        _ => Ok(Value::Empty),
+                    }
+                }
+                None => Ok(Value::Empty),
+            },
+            CellKind::Empty => Ok(Value::Empty),
+            CellKind::Number(d) => Ok(Value::Number(d)),
+            CellKind::Text(s) => Ok(Value::Text(s)),
+            CellKind::Bool(b) => Ok(Value::Bool(b)),
+            CellKind::Spilled { result, .. } => result,
             CellKind::Expr { result, expr } => {
-                if let None = result {
-                    let res = expr.evaluate(&mut |other_row, other_col| {
-                        Table::evaluate_cell(self, other_row, other_col, call_chain.clone())
+                if result.is_none() {
+                    self.report.evaluations += 1;
+                    *self
+                        .report
+                        .evaluations_per_cell
+                        .entry((row, col))
+                        .or_default() += 1;
+                    let context = self.context.clone();
+                    let started = self.profiling.then(Instant::now);
+                    let mut sub_evaluations = 0;
+                    let res = expr.evaluate(&context, &mut |other_row, other_col| {
+                        sub_evaluations += 1;
+                        self.evaluate_chained(other_row, other_col, chain)
                     });
+                    if let Some(started) = started {
+                        let profile = CellProfile {
+                            duration: started.elapsed(),
+                            sub_evaluations,
+                        };
+                        self.profiles.insert((row, col), profile);
+                    }
                     let res = match res.len() {
                         1 => res[0].clone(),
+                        _ if self.spill => self.spill_below(row, col, res),
                         _ => Err(TableError::MultipleCellReturn),
                     };
                     self.grid[(row, col)] = Ok(Cell {
@@ -77,7 +1000,7 @@ impl<'source, T: Evaluate> Table<'source, T> {
                             expr,
                             result: Some(res.clone()),
                         },
-                        source: cell.source,
+                        ..cell
                     });
                     return res;
                 };
@@ -86,47 +1009,2343 @@ impl<'source, T: Evaluate> Table<'source, T> {
         }
     }
 
-    pub fn run(&mut self) {
-        for col in 0..self.grid.cols {
-            for row in 0..self.grid.rows {
-                if let Ok(c) = self.grid[(row, col)].clone() {
-                    match c.kind {
-                        CellKind::Expr { expr, result } if result.is_none() => {
-                            let res = expr.evaluate(&mut |new_col, new_row| {
-                                Table::evaluate_cell(self, new_col, new_row, HashSet::new())
-                            });
-                            let res = match res.len() {
-                                1 => res[0].clone(),
-                                _ => Err(TableError::MultipleCellReturn),
-                            };
-                            self.grid[(row, col)] = Ok(Cell {
-                                kind: CellKind::Expr {
-                                    expr,
-                                    result: Some(res.clone()),
-                                },
-                                source: c.source,
-                            })
-                        }
-                        _ => {}
-                    }
+    /// Parses the `Unparsed` cell at `(row, col)` in place, storing a parse error like
+    /// `new_interpet` would.
+    fn parse_deferred(&mut self, row: usize, col: usize) -> TableResult<()> {
+        let Ok(
+            cell @ Cell {
+                kind: CellKind::Unparsed,
+                ..
+            },
+        ) = &self.grid[(row, col)]
+        else {
+            return Ok(());
+        };
+        let parsed = self.parse_deferred_source(&cell.source).map(|kind| Cell {
+            kind,
+            ..cell.clone()
+        });
+        self.report.lazy_parses += 1;
+        self.grid[(row, col)] = parsed.clone();
+        parsed.map(|_| ())
+    }
+
+    fn parse_deferred_source(&self, source: &str) -> TableResult<CellKind<T>> {
+        match self.lazy_parser {
+            Some(parse) => parse(source, &self.options),
+            None => Err(TableError::InvalidCell(format!(
+                "No parser for deferred cell {source}"
+            ))),
+        }
+    }
+
+    /// Evaluates the cell at `(row, col)` like `evaluate_cell` through a shared
+    /// borrow. Nothing is cached, so referenced formulas are recomputed on every
+    /// call; slower, but several threads can query one `Table` without locking.
+    /// Formulas returning several values fail with `MultipleCellReturn`.
+    pub fn evaluate_cell_pure(&self, row: usize, col: usize) -> TableResult<Value> {
+        self.evaluate_pure(row, col, &mut CallChain::default())
+    }
+
+    /// `evaluate_cell_pure` within `chain`, the cells already being evaluated.
+    fn evaluate_pure(&self, row: usize, col: usize, chain: &mut CallChain) -> TableResult<Value> {
+        let cell = self
+            .grid
+            .get(row, col)
+            .ok_or(TableError::CellOutOfBounds((row, col)))?;
+        chain.within((row, col), |chain| {
+            self.evaluate_pure_entered(row, col, cell, chain)
+        })
+    }
+
+    /// `evaluate_pure` once `(row, col)` has been added to `chain`.
+    fn evaluate_pure_entered(
+        &self,
+        row: usize,
+        col: usize,
+        cell: &TableResult<Cell<'source, T>>,
+        chain: &mut CallChain,
+    ) -> TableResult<Value> {
+        if let Some(value) = self.overrides.get(&(row, col)) {
+            return Ok(value.clone());
+        }
+        let cell = cell.as_ref().map_err(Clone::clone)?;
+        let parsed;
+        let kind = match &cell.kind {
+            CellKind::Unparsed => {
+                parsed = self.parse_deferred_source(&cell.source)?;
+                &parsed
+            }
+            kind => kind,
+        };
+        match kind {
+            CellKind::Empty => Ok(Value::Empty),
+            CellKind::Unparsed => Err(TableError::EmptyCellEvaluation),
+            CellKind::Number(d) => Ok(Value::Number(d.clone())),
+            CellKind::Text(s) => Ok(Value::Text(s.clone())),
+            CellKind::Bool(b) => Ok(Value::Bool(*b)),
+            CellKind::Spilled { result, .. } => result.clone(),
+            CellKind::Expr { expr, .. } => {
+                let res = expr.evaluate(&self.context, &mut |other_row, other_col| {
+                    self.evaluate_pure(other_row, other_col, chain)
+                });
+                match <[_; 1]>::try_from(res) {
+                    Ok([res]) => res,
+                    Err(_) => Err(TableError::MultipleCellReturn),
                 }
             }
         }
     }
-}
 
-impl<'source, T: Evaluate> std::fmt::Display for Table<'source, T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        for row in 0..self.grid.rows {
-            for col in 0..self.grid.cols {
-                match self.grid[(row, col)].clone() {
-                    Ok(c) => write!(f, "{c}")?,
-                    Err(e) => write!(f, "{e}")?,
-                };
-                write!(f, "|")?;
+    /// The nearest unevaluated formula above `(row, col)` with only empty cells in between.
+    fn spill_anchor_above(&self, row: usize, col: usize) -> Option<usize> {
+        for anchor in (0..row).rev() {
+            match &self.grid[(anchor, col)] {
+                Ok(Cell {
+                    kind: CellKind::Empty,
+                    ..
+                }) => continue,
+                Ok(Cell {
+                    kind: CellKind::Expr { result: None, .. } | CellKind::Unparsed,
+                    ..
+                }) => return Some(anchor),
+                _ => return None,
             }
-            write!(f, "\n")?;
+        }
+        None
+    }
+
+    /// Writes all but the first value into the cells below `(row, col)` and
+    /// returns the first, or a `SpillConflict` naming the first blocking cell.
+    fn spill_below(
+        &mut self,
+        row: usize,
+        col: usize,
+        results: Vec<TableResult<Value>>,
+    ) -> TableResult<Value> {
+        for offset in 1..results.len() {
+            let target = (row + offset, col);
+            if target.0 >= self.grid.rows {
+                return Err(TableError::SpillConflict(target));
+            }
+            match &self.grid[target] {
+                Ok(Cell {
+                    kind: CellKind::Empty,
+                    ..
+                }) => {}
+                Ok(Cell {
+                    kind: CellKind::Spilled { origin, .. },
+                    ..
+                }) if *origin == (row, col) => {}
+                _ => return Err(TableError::SpillConflict(target)),
+            }
+        }
+
+        let mut results = results.into_iter();
+        let first = results
+            .next()
+            .unwrap_or(Err(TableError::EmptyCellEvaluation));
+        for (offset, result) in results.enumerate() {
+            let target = (row + offset + 1, col);
+            let format = self.grid[target]
+                .as_ref()
+                .ok()
+                .and_then(|cell| cell.format.clone());
+            self.grid[target] = Ok(Cell {
+                kind: CellKind::Spilled {
+                    origin: (row, col),
+                    result,
+                },
+                source: "".into(),
+                format,
+            });
+        }
+        first
+    }
+
+    /// The sum of the numeric cells in each column, evaluating formulas as needed.
+    /// Empty, text and boolean cells are skipped; an error in a column is returned for it.
+    pub fn column_sums(&mut self) -> Vec<TableResult<Number>> {
+        (0..self.grid.cols)
+            .map(|col| self.sum_cells((0..self.grid.rows).map(|row| (row, col)).collect()))
+            .collect()
+    }
+
+    /// The sum of the numeric cells in each row, as in `column_sums`.
+    pub fn row_sums(&mut self) -> Vec<TableResult<Number>> {
+        (0..self.grid.rows)
+            .map(|row| self.sum_cells((0..self.grid.cols).map(|col| (row, col)).collect()))
+            .collect()
+    }
+
+    /// The cumulative sum down column `col`, one total per row, evaluating
+    /// formulas as needed. Empty, text and boolean cells add nothing; a formula
+    /// error is returned.
+    pub fn running_total(&mut self, col: usize) -> TableResult<Vec<Number>> {
+        self.check_in_bounds(0, col)?;
+        let mut total = Number::from(0);
+        let mut totals = Vec::with_capacity(self.grid.rows);
+        for row in 0..self.grid.rows {
+            match self.evaluate_cell(row, col) {
+                Ok(Value::Number(d)) => total = self.context.clamp(total + d),
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+            totals.push(total.clone());
+        }
+        Ok(totals)
+    }
+
+    /// The values of the `(rows, cols)` range with rows and columns swapped, so
+    /// `a1:c2` (2 x 3) becomes a 3 x 2 grid. Formulas are evaluated as needed.
+    pub fn transpose_range(
+        &mut self,
+        (rows, cols): (Range<usize>, Range<usize>),
+    ) -> TableResult<Grid<TableResult<Value>>> {
+        self.check_range_in_bounds(&(rows.clone(), cols.clone()))?;
+        let mut values = Vec::with_capacity(rows.len() * cols.len());
+        for row in rows.clone() {
+            for col in cols.clone() {
+                values.push(self.evaluate_cell(row, col));
+            }
+        }
+        Ok(Grid::new(rows.len(), cols.len(), values).transpose())
+    }
+
+    /// The distinct numbers in the `(rows, cols)` range, in first-seen order
+    /// (down each column, then across). `2` and `2.0` count as the same value.
+    /// Empty, text and boolean cells are skipped; a formula error is returned.
+    pub fn distinct_values(
+        &mut self,
+        (rows, cols): (Range<usize>, Range<usize>),
+    ) -> TableResult<Vec<Number>> {
+        self.check_range_in_bounds(&(rows.clone(), cols.clone()))?;
+        let mut seen = BTreeSet::new();
+        let mut values = Vec::new();
+        for col in cols {
+            for row in rows.clone() {
+                match self.evaluate_cell(row, col) {
+                    Ok(Value::Number(d)) => {
+                        if seen.insert(d.clone()) {
+                            values.push(d);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    fn sum_cells(&mut self, cells: Vec<(usize, usize)>) -> TableResult<Number> {
+        let mut total = Number::from(0);
+        for (row, col) in cells {
+            match self.evaluate_cell(row, col) {
+                Ok(Value::Number(d)) => total = self.context.clamp(total + d),
+                Ok(_) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(total)
+    }
+
+    /// Evaluates every formula that has no cached result. Cells are evaluated in
+    /// `evaluation_order`, so each one's references are already cached and the
+    /// recursion stays shallow even on long chains. A table with a cycle is
+    /// evaluated column by column instead, each cell evaluating its references
+    /// on demand. Cells of a lazy table are parsed first, once, so ordering
+    /// them does not parse them again.
+    pub fn run(&mut self) {
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                let _ = self.parse_deferred(row, col);
+            }
+        }
+        let order = self.evaluation_order().unwrap_or_else(|_| {
+            (0..self.grid.cols)
+                .flat_map(|col| (0..self.grid.rows).map(move |row| (row, col)))
+                .collect()
+        });
+        for (row, col) in order {
+            if let Ok(Cell {
+                kind: CellKind::Expr { result: None, .. },
+                ..
+            }) = self.grid[(row, col)]
+            {
+                let _ = self.evaluate_cell(row, col);
+            }
+        }
+    }
+
+    /// Evaluates the table allowing circular references: every formula is
+    /// recomputed from the previous iteration's formula values (starting at 0)
+    /// until no result moves by more than `tolerance`. Returns the number of
+    /// iterations, or an error if `max_iters` is reached first; the last
+    /// iteration's results are kept either way.
+    pub fn run_iterative(&mut self, max_iters: usize, tolerance: Number) -> TableResult<usize> {
+        let mut formulas = Vec::new();
+        for col in 0..self.grid.cols {
+            for row in 0..self.grid.rows {
+                let _ = self.parse_deferred(row, col);
+                if let Ok(Cell {
+                    kind: CellKind::Expr { expr, .. },
+                    ..
+                }) = &self.grid[(row, col)]
+                {
+                    formulas.push(((row, col), expr.clone()));
+                }
+            }
+        }
+        let mut previous: HashMap<(usize, usize), TableResult<Value>> = formulas
+            .iter()
+            .map(|(coord, _)| (*coord, Ok(Value::Number(Number::from(0)))))
+            .collect();
+
+        let context = self.context.clone();
+        for iteration in 1..=max_iters {
+            let mut current = HashMap::with_capacity(formulas.len());
+            for (coord, expr) in formulas.iter() {
+                let res =
+                    expr.evaluate(&context, &mut |row, col| match previous.get(&(row, col)) {
+                        Some(value) => value.clone(),
+                        None => self.evaluate_cell(row, col),
+                    });
+                let res = match res.len() {
+                    1 => res[0].clone(),
+                    _ => Err(TableError::MultipleCellReturn),
+                };
+                current.insert(*coord, res);
+            }
+
+            let converged = current
+                .iter()
+                .all(|(coord, value)| match (value, &previous[coord]) {
+                    (Ok(Value::Number(a)), Ok(Value::Number(b))) => (a - b).abs() <= tolerance,
+                    (Ok(a), Ok(b)) => a == b,
+                    (Err(a), Err(b)) => a.to_string() == b.to_string(),
+                    _ => false,
+                });
+            previous = current;
+            if converged {
+                self.store_results(previous);
+                return Ok(iteration);
+            }
+        }
+        self.store_results(previous);
+        Err(TableError::runtime_error(format!(
+            "Iterative calculation did not converge after {max_iters} iterations"
+        )))
+    }
+
+    /// Adjusts the number in `changing` until the formula in `target` evaluates
+    /// within `tolerance` of `desired`, using the secant method from the cell's
+    /// current value. Returns the value found, which is left in `changing`. On
+    /// any error, including reaching `max_iters`, `changing` is restored to what
+    /// it held before. Suited to targets that are monotonic in `changing`.
+    pub fn goal_seek(
+        &mut self,
+        target: (usize, usize),
+        desired: Number,
+        changing: (usize, usize),
+        max_iters: usize,
+        tolerance: Number,
+    ) -> TableResult<Number> {
+        for (row, col) in [target, changing] {
+            self.check_in_bounds(row, col)?;
+        }
+        let _ = self.parse_deferred(changing.0, changing.1);
+        let original = self.grid[changing].clone();
+        let result = self.secant_search(target, desired, changing, max_iters, tolerance);
+        if result.is_err() {
+            self.grid[changing] = original;
+            self.invalidate();
+        }
+        result
+    }
+
+    /// The secant iteration behind `goal_seek`, leaving its last guess in `changing`.
+    fn secant_search(
+        &mut self,
+        target: (usize, usize),
+        desired: Number,
+        changing: (usize, usize),
+        max_iters: usize,
+        tolerance: Number,
+    ) -> TableResult<Number> {
+        let mut x0 = match self.grid[changing].as_ref().map(|cell| &cell.kind) {
+            Ok(CellKind::Number(d)) => d.clone(),
+            Ok(CellKind::Empty) => Number::from(0),
+            _ => {
+                return Err(TableError::runtime_error(format!(
+                    "Goal seek can only change a number cell, not {}",
+                    cell_name(changing.0, changing.1)
+                )))
+            }
+        };
+        let mut f0 = self.goal_seek_error(target, &desired, changing, &x0)?;
+        let mut x1 = x0.clone() + Number::from(1);
+        for _ in 0..max_iters {
+            if f0.abs() <= tolerance {
+                self.goal_seek_error(target, &desired, changing, &x0)?;
+                return Ok(x0);
+            }
+            let f1 = self.goal_seek_error(target, &desired, changing, &x1)?;
+            if f1 == f0 {
+                return Err(TableError::runtime_error(format!(
+                    "Goal seek stalled: {} does not change with {}",
+                    cell_name(target.0, target.1),
+                    cell_name(changing.0, changing.1)
+                )));
+            }
+            let x2 = x1.clone() - f1.clone() * (x1.clone() - x0) / (f1.clone() - f0);
+            (x0, f0, x1) = (x1, f1, x2);
+        }
+        Err(TableError::runtime_error(format!(
+            "Goal seek did not converge after {max_iters} iterations"
+        )))
+    }
+
+    /// Writes `value` into `changing` and returns how far `target` then is from `desired`.
+    fn goal_seek_error(
+        &mut self,
+        target: (usize, usize),
+        desired: &Number,
+        changing: (usize, usize),
+        value: &Number,
+    ) -> TableResult<Number> {
+        if let Ok(cell) = &mut self.grid[changing] {
+            cell.source = Cow::Owned(value.to_plain_string());
+            cell.kind = CellKind::Number(value.clone());
+        }
+        self.invalidate();
+        let result = self.evaluate_cell(target.0, target.1)?;
+        Ok(result.as_number()? - desired.clone())
+    }
+
+    fn store_results(&mut self, results: HashMap<(usize, usize), TableResult<Value>>) {
+        for (coord, res) in results {
+            if let Ok(Cell {
+                kind: CellKind::Expr { result, .. },
+                ..
+            }) = &mut self.grid[coord]
+            {
+                *result = Some(res);
+            }
+        }
+    }
+
+    /// Runs the table and returns a snapshot of every cell's typed value, as
+    /// `get_value` would report it. The table keeps its formulas.
+    pub fn evaluate_all(&mut self) -> Grid<TableResult<Value>> {
+        self.run();
+        let values = (0..self.grid.rows)
+            .flat_map(|row| (0..self.grid.cols).map(move |col| (row, col)))
+            .map(|(row, col)| self.get_value(row, col))
+            .collect();
+        Grid::new(self.grid.rows, self.grid.cols, values)
+    }
+
+    /// Advances a time-stepped model by one step: the values from the last run
+    /// become what `prev(cell)` returns, then the table is recalculated. Before
+    /// the first step `prev` gives 0.
+    pub fn run_step(&mut self) {
+        let mut previous = BTreeMap::new();
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                if let Ok(value) = self.get_value(row, col) {
+                    previous.insert((row, col), value);
+                }
+            }
+        }
+        Arc::make_mut(&mut self.context).previous = previous;
+        self.invalidate();
+        self.run();
+    }
+
+    /// Runs the table like `run` and reports the formula evaluations it performed.
+    pub fn run_with_report(&mut self) -> RunReport {
+        self.report = RunReport::default();
+        self.run();
+        std::mem::take(&mut self.report)
+    }
+}
+
+impl<'source, T: Evaluate> Table<'source, T> {
+    /// Renders the table as an HTML `<table>`, marking error cells with `class="error"`.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<table>\n");
+        for row in 0..self.grid.rows {
+            html.push_str("  <tr>");
+            for col in 0..self.grid.cols {
+                let (class, content) = match &self.grid[(row, col)] {
+                    Ok(c) if c.is_error() => (" class=\"error\"", c.to_string()),
+                    Ok(c) => ("", c.to_string()),
+                    Err(e) => (" class=\"error\"", e.to_string()),
+                };
+                let style = match &self.grid[(row, col)] {
+                    Ok(Cell {
+                        format: Some(format),
+                        ..
+                    }) => format!(" style=\"{}\"", escape_html(&css_style(format))),
+                    _ => String::new(),
+                };
+                html.push_str(&format!("<td{class}{style}>{}</td>", escape_html(&content)));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+        html
+    }
+}
+
+/// The raw cell sources of a table, in row-major order.
+struct SourceFields<'source> {
+    fields: Vec<Cow<'source, str>>,
+    rows: usize,
+    cols: usize,
+}
+
+/// Splits `source` into rows and cells, checking that every row has the same
+/// number of cells and that the table fits in `(max_rows, max_cols)`.
+fn split_source(
+    source: &str,
+    (max_rows, max_cols): (usize, usize),
+    delimiter: char,
+) -> TableResult<SourceFields<'_>> {
+    let mut fields = Vec::new();
+    let mut rows = 0;
+
+    let mut previous_cols = None;
+    for row in source.lines() {
+        let row_fields = split_row(row, delimiter);
+        if rows == max_rows || row_fields.len() > max_cols {
+            return Err(TableError::runtime_error("table too large"));
+        }
+        match (previous_cols, row_fields.len()) {
+            (None, c) => previous_cols = Some(c),
+            (Some(p), c) if p != c => Err(TableError::MismatchedColumns)?,
+            _ => {}
+        }
+        fields.extend(row_fields);
+        rows += 1;
+    }
+    match previous_cols {
+        None => Err(TableError::EmptyTable),
+        Some(cols) => Ok(SourceFields { fields, rows, cols }),
+    }
+}
+
+/// Splits a source row on `delimiter`, treating `\` followed by the delimiter
+/// as a literal delimiter and `\\` as a literal backslash. Fields without
+/// escapes borrow from `row`.
+fn split_row(row: &str, delimiter: char) -> Vec<Cow<'_, str>> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut unescaped: Option<String> = None;
+    let mut chars = row.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((j, next)) = chars
+                    .clone()
+                    .next()
+                    .filter(|&(_, next)| next == delimiter || next == '\\')
+                {
+                    chars.next();
+                    let field = unescaped.get_or_insert_with(String::new);
+                    field.push_str(&row[start..i]);
+                    field.push(next);
+                    start = j + next.len_utf8();
+                }
+            }
+            c if c == delimiter => {
+                fields.push(field(row, start, i, unescaped.take()));
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    fields.push(field(row, start, row.len(), unescaped));
+    fields
+}
+
+fn field(row: &str, start: usize, end: usize, unescaped: Option<String>) -> Cow<'_, str> {
+    match unescaped {
+        Some(mut field) => {
+            field.push_str(&row[start..end]);
+            Cow::Owned(field)
+        }
+        None => Cow::Borrowed(&row[start..end]),
+    }
+}
+
+fn css_style(format: &CellFormat) -> String {
+    let mut declarations = Vec::new();
+    if format.bold {
+        declarations.push("font-weight: bold".to_string());
+    }
+    if format.italic {
+        declarations.push("font-style: italic".to_string());
+    }
+    if let Some(color) = &format.color {
+        declarations.push(format!("color: {color}"));
+    }
+    declarations.join("; ")
+}
+
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl<'source, T: Evaluate> Table<'source, T> {
+    /// Renders the table for reading, padding every column to its widest cell
+    /// so that empty cells show up as blank space.
+    pub fn to_aligned_string(&self) -> String {
+        let contents = Grid::new(
+            self.grid.rows,
+            self.grid.cols,
+            (0..self.grid.rows * self.grid.cols)
+                .map(
+                    |i| match &self.grid[(i / self.grid.cols, i % self.grid.cols)] {
+                        Ok(c) => c.to_string(),
+                        Err(e) => e.to_string(),
+                    },
+                )
+                .collect(),
+        );
+        let widths = (0..contents.cols)
+            .map(|col| {
+                (0..contents.rows)
+                    .map(|row| contents[(row, col)].chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(1)
+            })
+            .collect::<Vec<_>>();
+
+        let mut aligned = String::new();
+        for row in 0..contents.rows {
+            for (col, width) in widths.iter().enumerate() {
+                aligned.push_str(&format!("{:<width$}|", contents[(row, col)]));
+            }
+            aligned.push('\n');
+        }
+        aligned
+    }
+
+    /// The table as CSV, one line per row with each cell shown as in `Display`:
+    /// formulas by their result or error once evaluated. Fields containing a
+    /// comma, quote or line break are quoted as in RFC 4180.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+        for row in 0..self.grid.rows {
+            let fields = (0..self.grid.cols)
+                .map(|col| match &self.grid[(row, col)] {
+                    Ok(c) => csv_field(&c.to_string()),
+                    Err(e) => csv_field(&e.to_string()),
+                })
+                .collect::<Vec<_>>();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    /// Writes the table in the `|` separated format it is read from, with every
+    /// evaluated formula baked to its value so the output reads back as plain
+    /// cells. Text is written with a leading `'`, and cells holding an error as
+    /// the text `#ERR`. Formulas that have not been evaluated yet (see `run`)
+    /// keep their source; formatting directives are not written.
+    pub fn write_to<W: Write>(&self, mut w: W) -> std::io::Result<()> {
+        for row in 0..self.grid.rows {
+            let fields = (0..self.grid.cols)
+                .map(|col| self.baked_field(row, col))
+                .collect::<Vec<_>>();
+            writeln!(w, "{}", fields.join("|"))?;
+        }
+        Ok(())
+    }
+
+    fn baked_field(&self, row: usize, col: usize) -> String {
+        match &self.grid[(row, col)] {
+            Ok(Cell {
+                kind: CellKind::Unparsed | CellKind::Expr { result: None, .. },
+                source,
+                ..
+            }) => escape_field(source),
+            Ok(_) => match self.get_value(row, col) {
+                Ok(Value::Number(d)) => d.to_string(),
+                Ok(Value::Text(s)) => escape_field(&format!("'{s}")),
+                Ok(Value::Bool(b)) => b.to_string(),
+                Ok(Value::Empty) => String::new(),
+                Err(_) => "'#ERR".to_string(),
+            },
+            Err(_) => "'#ERR".to_string(),
+        }
+    }
+}
+
+/// The cells being evaluated, innermost last, to detect and report cycles
+/// without copying the chain for every reference.
+#[derive(Debug, Default)]
+struct CallChain {
+    cells: HashSet<(usize, usize)>,
+    path: Vec<(usize, usize)>,
+}
+
+impl CallChain {
+    /// Runs `evaluate` with `cell` added to the chain, or fails with the cycle
+    /// if it is already there.
+    fn within<F>(&mut self, cell: (usize, usize), evaluate: F) -> TableResult<Value>
+    where
+        F: FnOnce(&mut Self) -> TableResult<Value>,
+    {
+        if !self.cells.insert(cell) {
+            return Err(cycle_error(&self.path, cell));
+        }
+        self.path.push(cell);
+        let res = evaluate(self);
+        self.path.pop();
+        self.cells.remove(&cell);
+        res
+    }
+}
+
+/// The `RecursiveCellExpr` for reaching `cell` again after `path`: the part of
+/// the path from `cell`'s first visit, closed by `cell`.
+fn cycle_error(path: &[(usize, usize)], cell: (usize, usize)) -> TableError {
+    let start = path.iter().position(|&c| c == cell).unwrap_or(0);
+    let mut cycle = path[start..].to_vec();
+    cycle.push(cell);
+    TableError::RecursiveCellExpr(cycle)
+}
+
+/// Escapes `\` and `|` in a field so that `split_row` reads it back unchanged.
+fn escape_field(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Quotes a CSV field if it contains a comma, quote or line break, doubling
+/// any quotes inside it.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+impl<'source, T: Evaluate> std::fmt::Display for Table<'source, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in 0..self.grid.rows {
+            for col in 0..self.grid.cols {
+                match self.grid[(row, col)].clone() {
+                    Ok(c) => write!(f, "{c}")?,
+                    Err(e) => write!(f, "{e}")?,
+                };
+                write!(f, "|")?;
+            }
+            writeln!(f)?;
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_text_cell_not_numeric() {
+        let mut table = Table::new_interpet("'123|=a1+1|=sum(a1:a1)").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "123|Runtime Error: Expected a number in `a1 + 1`, got text \"123\"|Runtime Error: Expected a number in `sum(a1:a1)`, got text \"123\"|\n");
+        assert_eq!(
+            table.evaluate_cell(0, 0).unwrap(),
+            Value::Text("123".to_string())
+        );
+    }
+
+    #[test]
+    fn test_text_header_row() {
+        let source = "Item|Qty|Price\napples|3|0.5\npears|2|1.25\nTotal|=sum(b2:b3)|=a2 * 2";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        assert_eq!(table.get_value(0, 0).unwrap(), Value::Text("Item".into()));
+        assert_eq!(table.get_value(2, 0).unwrap(), Value::Text("pears".into()));
+        assert_eq!(table.get_number(3, 1).unwrap(), Number::from(5));
+        assert!(matches!(
+            table.get_value(3, 2),
+            Err(TableError::RuntimeError(msg)) if msg.contains("got text \"apples\"")
+        ));
+        assert!(table
+            .to_string()
+            .starts_with("Item|Qty|Price|\napples|3|0.5|\n"));
+    }
+
+    #[test]
+    fn test_block_sum() {
+        let mut table =
+            Table::new_interpet("1|2|3|=sum(a1:c3)\n4|5|6|=sum(c3:b2)\n7|8|9|=sum(a3:c3)").unwrap();
+        table.run();
+        assert_eq!(
+            table.evaluate_cell(0, 3).unwrap(),
+            Value::Number(Number::from(45))
+        );
+        assert_eq!(
+            table.evaluate_cell(1, 3).unwrap(),
+            Value::Number(Number::from(28))
+        );
+        assert_eq!(
+            table.evaluate_cell(2, 3).unwrap(),
+            Value::Number(Number::from(24))
+        );
+    }
+
+    #[test]
+    fn test_sum_of_single_values() {
+        let mut table =
+            Table::new_interpet("7|=sum(5)|=sum(a1)|=sum(a1:a1)|=sum(a1, 5, a1:a1)").unwrap();
+        table.run();
+        let number = |n: u32| Value::Number(Number::from(n));
+        assert_eq!(table.get_value(0, 1).unwrap(), number(5));
+        assert_eq!(table.get_value(0, 2).unwrap(), number(7));
+        assert_eq!(table.get_value(0, 3).unwrap(), number(7));
+        assert_eq!(table.get_value(0, 4).unwrap(), number(19));
+    }
+
+    #[test]
+    fn test_to_html() {
+        let mut table = Table::new_interpet("'<b>|2\n=b1*2|=a1+1").unwrap();
+        table.run();
+        let expected = [
+            "<table>",
+            "  <tr><td>&lt;b&gt;</td><td>2</td></tr>",
+            "  <tr><td>4</td><td class=\"error\">Runtime Error: Expected a number in `a1 + 1`, got text &quot;&lt;b&gt;&quot;</td></tr>",
+            "</table>",
+            "",
+        ];
+        assert_eq!(table.to_html(), expected.join("\n"));
+    }
+
+    #[test]
+    fn test_formatting_directives() {
+        let mut table = Table::new_interpet("{bold}1|{color:red}{italic}=a1*2|{bold}'x").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1|2|x|\n");
+        assert_eq!(
+            table.to_html(),
+            concat!(
+                "<table>\n",
+                "  <tr><td style=\"font-weight: bold\">1</td>",
+                "<td style=\"font-style: italic; color: red\">2</td>",
+                "<td style=\"font-weight: bold\">x</td></tr>\n",
+                "</table>\n"
+            )
+        );
+
+        let mut lazy =
+            Table::new_interpet_lazy("{bold}1|{color:red}{italic}=a1*2|{bold}'x").unwrap();
+        lazy.run();
+        assert_eq!(lazy.to_html(), table.to_html());
+    }
+
+    #[test]
+    fn test_spill_vertical() {
+        let mut table = Table::new_interpet("1|=a1:a3|=sum(b1:b3)\n2||\n3||").unwrap();
+        table.run();
+        assert!(matches!(
+            table.evaluate_cell(0, 1),
+            Err(TableError::MultipleCellReturn)
+        ));
+
+        let mut table = Table::new_interpet("1|=a1:a3|=sum(b1:b3)\n2||\n3||").unwrap();
+        table.set_spill(true);
+        table.run();
+        assert_eq!(table.to_string(), "1|1|6|\n2|2||\n3|3||\n");
+    }
+
+    #[test]
+    fn test_range_scalar_broadcast() {
+        let mut table = Table::new_interpet("1|=sum(a1:a3 + 1)|=sum(2 * a1:a3)\n2||\n3||").unwrap();
+        table.run();
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Number(Number::from(9))
+        );
+        assert_eq!(
+            table.get_value(0, 2).unwrap(),
+            Value::Number(Number::from(12))
+        );
+
+        let mut table = Table::new_interpet("1|=a1:a3 * 10\n2|\n3|").unwrap();
+        table.set_spill(true);
+        table.run();
+        assert_eq!(table.to_string(), "1|10|\n2|20|\n3|30|\n");
+
+        let mut table = Table::new_interpet("1|=a1:a2 + a1:a2\n2|").unwrap();
+        table.run();
+        assert!(table.get_value(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_spill_dependent_evaluated_first() {
+        let mut table = Table::new_interpet("=b3*2|=c1:c3|1\n1||2\n2||3").unwrap();
+        table.set_spill(true);
+        table.run();
+        assert_eq!(table.to_string(), "6|1|1|\n1|2|2|\n2|3|3|\n");
+    }
+
+    #[test]
+    fn test_spill_conflict() {
+        let mut table = Table::new_interpet("1|=a1:a3\n2|5\n3|").unwrap();
+        table.set_spill(true);
+        table.run();
+        assert!(matches!(
+            table.evaluate_cell(0, 1),
+            Err(TableError::SpillConflict((1, 1)))
+        ));
+
+        let mut table = Table::new_interpet("1|\n2|=a1:a2").unwrap();
+        table.set_spill(true);
+        table.run();
+        assert!(matches!(
+            table.evaluate_cell(1, 1),
+            Err(TableError::SpillConflict((2, 1)))
+        ));
+    }
+
+    #[test]
+    fn test_apply_formula_to_column() {
+        let mut table = Table::new_interpet("1|10|\n2|20|\n3|30|").unwrap();
+        table.apply_formula_to_column(2, "=a{r}+b{r}").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1|10|11|\n2|20|22|\n3|30|33|\n");
+
+        assert!(table.apply_formula_to_column(2, "=a{r}+").is_err());
+        assert!(matches!(
+            table.apply_formula_to_column(3, "=a{r}"),
+            Err(TableError::CellOutOfBounds((0, 3)))
+        ));
+    }
+
+    #[test]
+    fn test_labels_survive_row_insertion() {
+        let mut table = Table::new_interpet("'total|5\n=@total * 2|=@missing").unwrap();
+        table.label_cell(0, 1, "Total").unwrap();
+        table.run();
+        assert_eq!(
+            table.get_value(1, 0).unwrap(),
+            Value::Number(Number::from(10))
+        );
+        assert!(matches!(
+            table.get_value(1, 1),
+            Err(TableError::RuntimeError(msg)) if msg == "Unknown label `@missing`"
+        ));
+
+        table.insert_row(0);
+        table.run();
+        assert_eq!(table.rows(), 3);
+        assert_eq!(
+            table.get_value(2, 0).unwrap(),
+            Value::Number(Number::from(10))
+        );
+
+        assert!(table.label_cell(5, 0, "x").is_err());
+        assert!(table.label_cell(0, 0, "not a name").is_err());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_construction_matches_serial() {
+        let source = (0..200)
+            .map(|row| format!("{row}|=a{}*2|'r{row}|=1+", row + 1))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let serial = Table::new_interpet(&source).unwrap();
+        let parallel = Table::new_interpet_parallel(&source).unwrap();
+        assert_eq!(format!("{:?}", parallel.grid), format!("{:?}", serial.grid));
+        assert!(matches!(
+            Table::new_interpet_parallel("1|2\n3"),
+            Err(TableError::MismatchedColumns)
+        ));
+    }
+
+    #[test]
+    fn test_interpret_with_limits() {
+        let table = Table::new_interpet_with_limits("1|2\n3|4", 2, 2).unwrap();
+        assert_eq!((table.rows(), table.cols()), (2, 2));
+
+        let too_large = |source, max_rows, max_cols| {
+            matches!(
+                Table::new_interpet_with_limits(source, max_rows, max_cols),
+                Err(TableError::RuntimeError(msg)) if msg == "table too large"
+            )
+        };
+        assert!(too_large("1|2\n3|4\n5|6", 2, 2));
+        assert!(too_large("1|2|3", 2, 2));
+        // The row past the limit is rejected before it is even checked for a
+        // mismatched column count.
+        assert!(too_large("1\n2\n3|4|5|6", 2, 4));
+    }
+
+    #[test]
+    fn test_insert_row_and_column_shift_references() {
+        let mut table = Table::new_interpet("1|2\n3|4\n=a2*10|=sum(a1:b2)").unwrap();
+        table.insert_row(1);
+        table.set_cell(1, 0, "5").unwrap();
+        table.set_cell(1, 1, "6").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1|2|\n5|6|\n3|4|\n30|21|\n");
+        assert_eq!(table.dependencies_of(3, 0), [(2, 0)]);
+        assert_eq!(table.grid[(3, 1)].as_ref().unwrap().source, "=sum(a1:b3)");
+
+        table.insert_column(0);
+        table.run();
+        assert_eq!(table.to_string(), "|1|2|\n|5|6|\n|3|4|\n|30|21|\n");
+        assert_eq!(table.grid[(3, 1)].as_ref().unwrap().source, "=b3 * 10");
+
+        let options = ParseOptions {
+            formula_prefixes: vec!['=', '-'],
+            ..ParseOptions::default()
+        };
+        let mut table = Table::new_interpet_with_options("7|-a1\n1|2", options).unwrap();
+        table.insert_row(0);
+        assert_eq!(table.grid[(1, 1)].as_ref().unwrap().source, "-a2");
+    }
+
+    #[test]
+    fn test_absolute_references() {
+        let mut table = Table::new_interpet("1|2\n=$a$1 + a$1 + $b1|=sum($a$1:b$1)").unwrap();
+        table.run();
+        assert_eq!(table.get_number(1, 0).unwrap(), Number::from(4));
+        assert_eq!(table.get_number(1, 1).unwrap(), Number::from(3));
+
+        table.insert_row(0);
+        assert_eq!(
+            table.grid[(2, 0)].as_ref().unwrap().source,
+            "=$a$2 + a$2 + $b2"
+        );
+        assert_eq!(
+            table.grid[(2, 1)].as_ref().unwrap().source,
+            "=sum($a$2:b$2)"
+        );
+        table.run();
+        assert_eq!(table.get_number(2, 1).unwrap(), Number::from(3));
+    }
+
+    #[test]
+    fn test_to_matrix() {
+        let mut table = Table::new_interpet("1|2.5\n=a1*4|\n-3|=sum(a1:a3)").unwrap();
+        assert!(table.to_matrix(true).is_err());
+        table.run();
+        assert_eq!(
+            table.to_matrix(true).unwrap(),
+            [[1.0, 2.5], [4.0, 0.0], [-3.0, 2.0]]
+        );
+        assert!(matches!(
+            table.to_matrix(false),
+            Err(TableError::EmptyCellEvaluation)
+        ));
+        table.set_cell(1, 1, "'x").unwrap();
+        assert!(table.to_matrix(true).is_err());
+    }
+
+    #[test]
+    fn test_goal_seek() {
+        let mut table = Table::new_interpet("3|=a1*2\n0|=b1*b1-a2").unwrap();
+        let x = table
+            .goal_seek((0, 1), Number::from(10), (0, 0), 20, Number::from(0))
+            .unwrap();
+        assert_eq!(x, Number::from(5));
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Number(Number::from(10))
+        );
+        table.run();
+        assert_eq!(table.to_string(), "5|10|\n0|100|\n");
+
+        let tolerance = Number::from_str("0.000001").unwrap();
+        let x = table
+            .goal_seek((1, 1), Number::from(36), (0, 0), 50, tolerance)
+            .unwrap();
+        assert!((x - Number::from(3)).abs() <= Number::from_str("0.001").unwrap());
+
+        assert!(table
+            .goal_seek((0, 1), Number::from(1), (0, 1), 20, Number::from(0))
+            .is_err());
+
+        let mut table = Table::new_interpet("3|=1/a1").unwrap();
+        assert!(table
+            .goal_seek((0, 1), Number::from(0), (0, 0), 20, Number::from(0))
+            .is_err());
+        assert_eq!(table.grid[(0, 0)].as_ref().unwrap().source, "3");
+        assert_eq!(table.get_number(0, 0).unwrap(), Number::from(3));
+        table.run();
+        assert!(table.get_value(0, 1).is_ok());
+    }
+
+    #[test]
+    fn test_write_to_bakes_values() {
+        let source = "2|=a1*3|'a\\|b\n=sum(a1:b1)|=1+|=c1\ntrue|=a3|";
+        let mut table = Table::new_interpet(source).unwrap();
+        let mut unevaluated = Vec::new();
+        table.write_to(&mut unevaluated).unwrap();
+        assert_eq!(
+            String::from_utf8(unevaluated).unwrap(),
+            "2|=a1*3|'a\\|b\n=sum(a1:b1)|'#ERR|=c1\ntrue|=a3|\n"
+        );
+
+        table.run();
+        let mut baked = Vec::new();
+        table.write_to(&mut baked).unwrap();
+        let baked = String::from_utf8(baked).unwrap();
+        assert_eq!(baked, "2|6|'a\\|b\n8|'#ERR|'a\\|b\ntrue|true|\n");
+
+        let mut reread = Table::new_interpet(&baked).unwrap();
+        assert!(!(0..3).any(|row| (0..3).any(|col| reread.is_formula(row, col))));
+        reread.run();
+        for (row, col) in [(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)] {
+            assert_eq!(
+                reread.get_value(row, col).unwrap(),
+                table.get_value(row, col).unwrap()
+            );
+        }
+        assert_eq!(
+            reread.get_value(1, 1).unwrap(),
+            Value::Text("#ERR".to_string())
+        );
+    }
+
+    #[test]
+    fn test_override() {
+        let mut table = Table::new_interpet("2|=a1*10|=b1+1\n=b1|=a2*2|3").unwrap();
+        table
+            .set_override(0, 1, Value::Number(Number::from(7)))
+            .unwrap();
+        let report = table.run_with_report();
+        assert_eq!(table.to_string(), "2|7|8|\n7|14|3|\n");
+        assert_eq!(report.evaluations, 3);
+        assert!(!report.evaluations_per_cell.contains_key(&(0, 1)));
+
+        table
+            .set_override(1, 2, Value::Text("x".to_string()))
+            .unwrap();
+        assert_eq!(table.get_value(1, 2).unwrap(), Value::Text("x".to_string()));
+        assert!(table.set_override(2, 0, Value::Bool(true)).is_err());
+
+        table.clear_override(0, 1);
+        table.insert_row(0);
+        table.run();
+        assert_eq!(table.to_string(), "|||\n2|20|21|\n20|40|3|\n");
+        assert_eq!(table.get_value(2, 2).unwrap(), Value::Text("x".to_string()));
+    }
+
+    #[test]
+    fn test_empty_value() {
+        let source = "4||=max(a1:b1)|=count(a1:b1)|=type(b1)|=b1+1|=a1 & b1";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        assert_eq!(table.get_value(0, 1).unwrap(), Value::Empty);
+        assert_eq!(table.get_number(0, 2).unwrap(), Number::from(4));
+        assert_eq!(table.get_number(0, 3).unwrap(), Number::from(1));
+        assert_eq!(table.get_value(0, 4).unwrap(), Value::Text("empty".into()));
+        assert!(table.get_value(0, 5).is_err());
+        assert_eq!(table.get_value(0, 6).unwrap(), Value::Text("4".into()));
+
+        let mut baked = Vec::new();
+        table.write_to(&mut baked).unwrap();
+        assert_eq!(
+            String::from_utf8(baked).unwrap(),
+            "4||4|1|'empty|'#ERR|'4\n"
+        );
+    }
+
+    #[test]
+    fn test_from_grid_values() {
+        let values = (1..=6).map(Number::from).collect::<Vec<_>>();
+        let mut table = Table::from_grid_values(Grid::new(2, 3, values));
+        assert_eq!((table.rows(), table.cols()), (2, 3));
+        assert_eq!(
+            table.evaluate_cell(1, 0).unwrap(),
+            Value::Number(Number::from(4))
+        );
+        assert_eq!(table.to_string(), "1|2|3|\n4|5|6|\n");
+    }
+
+    #[test]
+    fn test_concat_vertical() {
+        let top = Table::new_interpet("1|2").unwrap();
+        let bottom = Table::new_interpet("3|=a1+b1\n5|6").unwrap();
+        let mut table = Table::concat_vertical(&[top, bottom]).unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1|2|\n3|3|\n5|6|\n");
+
+        let narrow = Table::new_interpet("1").unwrap();
+        let wide = Table::new_interpet("1|2").unwrap();
+        assert!(matches!(
+            Table::concat_vertical(&[narrow, wide]),
+            Err(TableError::MismatchedColumns)
+        ));
+        assert!(matches!(
+            Table::<Expr>::concat_vertical(&[]),
+            Err(TableError::EmptyTable)
+        ));
+    }
+
+    #[test]
+    fn test_concat_horizontal() {
+        let left = Table::new_interpet("1\n2").unwrap();
+        let right = Table::new_interpet("=a1+a2|4\n5|6").unwrap();
+        let mut table = Table::concat_horizontal(&[left, right]).unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1|3|4|\n2|5|6|\n");
+
+        let short = Table::new_interpet("1").unwrap();
+        let tall = Table::new_interpet("1\n2").unwrap();
+        assert!(matches!(
+            Table::concat_horizontal(&[short, tall]),
+            Err(TableError::MismatchedRows)
+        ));
+    }
+
+    #[test]
+    fn test_get_number() {
+        let mut table = Table::new_interpet("2.5|=a1*2|'x|").unwrap();
+        assert_eq!(
+            table.get_number(0, 0).unwrap(),
+            Number::from_str("2.5").unwrap()
+        );
+        assert!(table.get_number(0, 1).is_err());
+        table.run();
+        assert_eq!(table.get_number(0, 1).unwrap(), Number::from(5));
+        assert!(matches!(
+            table.get_number(0, 2),
+            Err(TableError::RuntimeError(_))
+        ));
+        assert!(matches!(
+            table.get_number(0, 3),
+            Err(TableError::EmptyCellEvaluation)
+        ));
+    }
+
+    #[test]
+    fn test_empty_and_set_cell() {
+        let mut table = Table::empty(3, 3);
+        assert_eq!(table.to_string(), "|||\n|||\n|||\n");
+
+        table.set_cell(1, 1, "5").unwrap();
+        table.set_cell(2, 2, "=b2*2").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "|||\n|5||\n||10|\n");
+
+        table.set_cell(1, 1, "7").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "|||\n|7||\n||14|\n");
+
+        assert!(table.set_cell(0, 0, "=(").is_err());
+        assert!(table.set_cell(3, 0, "1").is_err());
+    }
+
+    #[test]
+    fn test_set_cell_recalculates_dependents() {
+        let mut table = Table::new_interpet("1|=a1*2|=sum(a1:b1)\n4|=a2+1|=@total").unwrap();
+        table.label_cell(0, 2, "total").unwrap();
+        table.set_cell(0, 0, "2").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "2|4|6|\n4|5|6|\n");
+
+        table.set_cell(0, 0, "3").unwrap();
+        let report = table.run_with_report();
+        assert_eq!(table.to_string(), "3|6|9|\n4|5|9|\n");
+        assert_eq!(report.evaluations, 3);
+        assert!(!report.evaluations_per_cell.contains_key(&(1, 1)));
+
+        table.set_cell(1, 0, "=c1").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "3|6|9|\n9|10|9|\n");
+    }
+
+    #[test]
+    fn test_clamp() {
+        let mut table = Table::new_interpet("6|7|=sum(a1:b1)|=a1-b1").unwrap();
+        table
+            .set_clamp(Some((Number::from(0), Number::from(10))))
+            .unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "6|7|10|0|\n");
+
+        table
+            .set_clamp(Some((Number::from(-100), Number::from(100))))
+            .unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "6|7|13|-1|\n");
+
+        assert!(table
+            .set_clamp(Some((Number::from(1), Number::from(0))))
+            .is_err());
+    }
+
+    #[test]
+    fn test_type_function() {
+        let source = "4|'x|true||=1/0|=a1*2\n=type(a1)|=type(b1)|=type(c1)|=type(d1)|=type(e1)|=type(f1)\n=type(a1:b1)|=type(\"s\")|=type(1 + 1)|=type(a3)||";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let text = |s: &str| Value::Text(s.to_string());
+        let types = ["number", "text", "bool", "empty", "error", "number"];
+        for (col, expected) in types.into_iter().enumerate() {
+            assert_eq!(table.get_value(1, col).unwrap(), text(expected));
+        }
+        assert!(matches!(
+            table.get_value(2, 0),
+            Err(TableError::MultipleCellReturn)
+        ));
+        assert_eq!(table.get_value(2, 1).unwrap(), text("text"));
+        assert_eq!(table.get_value(2, 2).unwrap(), text("number"));
+        assert_eq!(table.get_value(2, 3).unwrap(), text("error"));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let source = "4|-2|\n1|7|\n9|3|'x\n=max(a1, b1:b3)|=min(a1:b3, 0.5)|=max(c1:c2)\n=min(a2)|=max(a1:a3, c3)|=min(-a1, b1 * 3)";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        let number = |n: &str| Value::Number(Number::from_str(n).unwrap());
+        assert_eq!(value(3, 0).unwrap(), number("7"));
+        assert_eq!(value(3, 1).unwrap(), number("-2"));
+        assert!(matches!(
+            value(3, 2),
+            Err(TableError::RuntimeError(msg)) if msg == "No values in `max(c1:c2)`"
+        ));
+        assert_eq!(value(4, 0).unwrap(), number("1"));
+        assert!(value(4, 1).is_err());
+        assert_eq!(value(4, 2).unwrap(), number("-6"));
+    }
+
+    #[test]
+    fn test_maxifs_minifs() {
+        let source = "1|10\n2|30\n1|25\n2|5\n=maxifs(b1:b4, a1:a4, 1)|=minifs(b1:b4, a1:a4, a2)\n=maxifs(b1:b4, a1:a4, 3)|=minifs(b1:b3, a1:a4, 2)";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        assert_eq!(
+            table.evaluate_cell(4, 0).unwrap(),
+            Value::Number(Number::from(25))
+        );
+        assert_eq!(
+            table.evaluate_cell(4, 1).unwrap(),
+            Value::Number(Number::from(5))
+        );
+        assert_eq!(
+            table.evaluate_cell(5, 0).unwrap(),
+            Value::Number(Number::from(0))
+        );
+        assert!(table.evaluate_cell(5, 1).is_err());
+    }
+
+    #[test]
+    fn test_sumproduct() {
+        let source = "2|10|1\n3|20|1\n5|30|0\n=sumproduct(a1:a3, b1:b3)|=sumproduct(a1:a3, b1:b3, c1:c3)|=sumproduct(a1:a3)\n=sumproduct(a1:a3, b1:b2)|=sumproduct(a1:b3, b1:c3)|";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        assert_eq!(value(3, 0).unwrap(), Value::Number(Number::from(230)));
+        assert_eq!(value(3, 1).unwrap(), Value::Number(Number::from(80)));
+        assert_eq!(value(3, 2).unwrap(), Value::Number(Number::from(10)));
+        assert!(value(4, 0)
+            .unwrap_err()
+            .to_string()
+            .contains("Ranges have different sizes"));
+        assert_eq!(value(4, 1).unwrap(), Value::Number(Number::from(260)));
+    }
+
+    #[test]
+    fn test_cell_profile() {
+        let mut table = Table::new_interpet("1|2|3\n=a1+b1*c1|=sum(a1:c1)+a2|=a1").unwrap();
+        table.run();
+        assert_eq!(table.cell_profile(1, 0), None);
+
+        table.set_profiling(true);
+        table.set_cell(0, 0, "4").unwrap();
+        table.run();
+        let sub_evaluations = |col| table.cell_profile(1, col).unwrap().sub_evaluations;
+        assert_eq!(sub_evaluations(0), 3);
+        assert_eq!(sub_evaluations(1), 4);
+        assert_eq!(sub_evaluations(2), 1);
+        assert_eq!(table.cell_profile(0, 0), None);
+
+        table.set_profiling(false);
+        assert_eq!(table.cell_profile(1, 0), None);
+    }
+
+    #[test]
+    fn test_interpret_lenient() {
+        let source = "1|=1+|3\n=sum(a1:c1|5|=a1*2\n7|8";
+        assert!(matches!(
+            Table::new_interpet(source),
+            Err(TableError::MismatchedColumns)
+        ));
+
+        let (mut table, errors) = Table::new_interpet_lenient(source);
+        let errors = errors
+            .iter()
+            .map(|(coord, e)| (*coord, e.code()))
+            .collect::<Vec<_>>();
+        let ast_error = TableError::ErrorConstructingAst(String::new()).code();
+        assert_eq!(
+            errors,
+            [
+                ((0, 1), ast_error),
+                ((1, 0), ast_error),
+                ((2, 2), TableError::MismatchedColumns.code())
+            ]
+        );
+        assert_eq!((table.rows(), table.cols()), (3, 3));
+        table.run();
+        assert_eq!(
+            table.get_value(1, 2).unwrap(),
+            Value::Number(Number::from(2))
+        );
+        assert_eq!(
+            table.get_value(2, 1).unwrap(),
+            Value::Number(Number::from(8))
+        );
+        assert!(table.is_empty(2, 2));
+
+        let (table, errors) = Table::new_interpet_lenient("");
+        assert_eq!((table.rows(), table.cols()), (0, 0));
+        assert!(matches!(errors[..], [((0, 0), TableError::EmptyTable)]));
+    }
+
+    #[test]
+    fn test_date_parts() {
+        let mut table = Table::new_interpet(
+            "'2024-03-15|=year(a1)|=month(a1)|=day(a1)|=weekday(a1)\n5|=year(a2)|=day(\"2024-02-30\")|=month(b1)|=weekday(\"2000-02-29\")\n2023-12-31|=year(a3)|=month(a3)|=day(a3)|=weekday(a3)",
+        )
+        .unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        assert_eq!(value(0, 1).unwrap(), Value::Number(Number::from(2024)));
+        assert_eq!(value(0, 2).unwrap(), Value::Number(Number::from(3)));
+        assert_eq!(value(0, 3).unwrap(), Value::Number(Number::from(15)));
+        assert_eq!(value(0, 4).unwrap(), Value::Number(Number::from(6)));
+        assert!(value(1, 1)
+            .unwrap_err()
+            .to_string()
+            .contains("Expected a date in `year(a2)`"));
+        assert!(value(1, 2).is_err());
+        assert!(value(1, 3).is_err());
+        assert_eq!(value(1, 4).unwrap(), Value::Number(Number::from(3)));
+        assert_eq!(value(2, 1).unwrap(), Value::Number(Number::from(2023)));
+        assert_eq!(value(2, 2).unwrap(), Value::Number(Number::from(12)));
+        assert_eq!(value(2, 3).unwrap(), Value::Number(Number::from(31)));
+        assert_eq!(value(2, 4).unwrap(), Value::Number(Number::from(1)));
+    }
+
+    #[test]
+    fn test_prev_steps() {
+        let mut table =
+            Table::new_interpet("=prev(a1)+1|=prev(a1)*10+b2|=a1\n|2|=prev(c1)").unwrap();
+        assert!(table.validate_acyclic().is_ok());
+        table.run();
+        assert_eq!(table.to_string(), "1|2|1|\n|2|0|\n");
+
+        table.run_step();
+        assert_eq!(table.to_string(), "2|12|2|\n|2|1|\n");
+        table.run_step();
+        assert_eq!(table.to_string(), "3|22|3|\n|2|2|\n");
+
+        let mut table = Table::new_interpet("=prev(1)|=prev(a1:a2)\n|").unwrap();
+        table.run();
+        assert!(table.get_value(0, 0).is_err());
+        assert!(table.get_value(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_coerce_text_numbers() {
+        let mut table =
+            Table::new_interpet("'42|=a1+1|=sum(a1:a2)\n' 1.5 |=-a2|'x\n=c2*2|=b1%|").unwrap();
+        table.run();
+        for (row, col) in [(0, 1), (0, 2), (1, 1), (2, 1)] {
+            assert!(matches!(
+                table.get_value(row, col),
+                Err(TableError::RuntimeError(msg)) if msg.contains("got text")
+            ));
+        }
+
+        table.set_coerce_text_numbers(true);
+        table.run();
+        let number = |n: &str| Value::Number(Number::from_str(n).unwrap());
+        assert_eq!(table.get_value(0, 1).unwrap(), number("43"));
+        assert_eq!(table.get_value(0, 2).unwrap(), number("43.5"));
+        assert_eq!(table.get_value(1, 1).unwrap(), number("-1.5"));
+        assert_eq!(table.get_value(2, 1).unwrap(), number("0.43"));
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Text("42".to_string())
+        );
+        assert!(matches!(
+            table.get_value(2, 0),
+            Err(TableError::RuntimeError(msg)) if msg.contains("got text \"x\"")
+        ));
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut table = Table::new_interpet("3|4|=a1+b1*2|=sum(a1:b1)*(c1-1)").unwrap();
+        assert_eq!(
+            table.explain(0, 2),
+            "c1 = a1 + b1 * 2 = 11\n  a1 = 3\n  b1 * 2 = 8\n    b1 = 4"
+        );
+        assert_eq!(
+            table.explain(0, 3),
+            [
+                "d1 = sum(a1:b1) * (c1 - 1) = 70",
+                "  sum(a1:b1) = 7",
+                "    a1:b1 = [3, 4]",
+                "  c1 - 1 = 10",
+                "    c1 = 11",
+            ]
+            .join("\n")
+        );
+        assert_eq!(table.explain(0, 0), "a1 = 3");
+        assert_eq!(
+            table.explain(99, 99),
+            "cv100 = Reference to a cell outside the table: (99, 99)"
+        );
+    }
+
+    #[test]
+    fn test_dependencies_of() {
+        let table = Table::new_interpet("1|2|=a1*b1\n3|4|=sum(a1:b2)+a1").unwrap();
+        assert_eq!(table.dependencies_of(0, 2), vec![(0, 0), (0, 1)]);
+        assert_eq!(
+            table.dependencies_of(1, 2),
+            vec![(0, 0), (1, 0), (0, 1), (1, 1)]
+        );
+        assert!(table.dependencies_of(0, 0).is_empty());
+        assert!(table.dependencies_of(99, 99).is_empty());
+    }
+
+    #[test]
+    fn test_dependencies_json() {
+        let table = Table::new_interpet("1|2|=a1+b1\n=sum(a1:b1)|=c1*2|=1+2").unwrap();
+        assert_eq!(
+            table.dependencies_json(),
+            r#"{"c1":["a1","b1"],"a2":["a1","b1"],"b2":["c1"],"c2":[]}"#
+        );
+        assert_eq!(
+            Table::new_interpet("1|2").unwrap().dependencies_json(),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn test_cell_kind_predicates() {
+        let source = "1|=a1*2|\n'x|=1+|true";
+        for table in [
+            Table::new_interpet(source).unwrap(),
+            Table::new_interpet_lazy(source).unwrap(),
+        ] {
+            let kinds = |row, col| {
+                (
+                    table.is_formula(row, col),
+                    table.is_number(row, col),
+                    table.is_empty(row, col),
+                )
+            };
+            assert_eq!(kinds(0, 0), (false, true, false));
+            assert_eq!(kinds(0, 1), (true, false, false));
+            assert_eq!(kinds(0, 2), (false, false, true));
+            assert_eq!(kinds(1, 0), (false, false, false));
+            assert_eq!(kinds(1, 1), (false, false, false));
+            assert_eq!(kinds(1, 2), (false, false, false));
+            assert_eq!(kinds(2, 0), (false, false, false));
+            assert_eq!(kinds(0, 3), (false, false, false));
+        }
+    }
+
+    #[test]
+    fn test_dependents_of() {
+        let table = Table::new_interpet("1|2|=a1*b1\n3|4|=sum(a1:b2)+a1").unwrap();
+        assert_eq!(table.dependents_of(0, 0), vec![(0, 2), (1, 2)]);
+        assert_eq!(table.dependents_of(1, 1), vec![(1, 2)]);
+        assert!(table.dependents_of(1, 2).is_empty());
+    }
+
+    #[test]
+    fn test_lotus_formula_prefixes() {
+        let options = ParseOptions {
+            formula_prefixes: vec!['+', '@'],
+            ..ParseOptions::default()
+        };
+        let mut table = Table::new_interpet_with_options("1|2|+a1+b1\n-3|4|", options).unwrap();
+        table.set_cell(1, 2, "@sum(a1:b2)").unwrap();
+        table.run();
+        assert_eq!(format!("{table}"), "1|2|3|\n-3|4|4|\n");
+    }
+
+    #[test]
+    fn test_get_value_is_typed() {
+        let mut table = Table::new_interpet("2|'total|TRUE|=a1*3|=false").unwrap();
+        assert!(table.get_value(0, 3).is_err());
+        table.run();
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Number(Number::from(2))
+        );
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Text("total".to_string())
+        );
+        assert_eq!(table.get_value(0, 2).unwrap(), Value::Bool(true));
+        assert_eq!(
+            table.get_value(0, 3).unwrap(),
+            Value::Number(Number::from(6))
+        );
+        assert_eq!(table.get_value(0, 4).unwrap(), Value::Bool(false));
+        assert!(table.get_value(1, 0).is_err());
+    }
+
+    #[test]
+    fn test_column_and_row_sums() {
+        let mut table =
+            Table::new_interpet("'item|'qty|'price\n'a|2|1.5\n'b||=b2*2\n'c|3|0.5").unwrap();
+        let sums = |sums: Vec<TableResult<Number>>| {
+            sums.into_iter().map(|s| s.unwrap()).collect::<Vec<_>>()
+        };
+        let numbers = |numbers: &[&str]| {
+            numbers
+                .iter()
+                .map(|n| Number::from_str(n).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(sums(table.column_sums()), numbers(&["0", "5", "6"]));
+        assert_eq!(sums(table.row_sums()), numbers(&["0", "3.5", "4", "3.5"]));
+
+        let mut table = Table::new_interpet("1|=a1+b2\n2|'x").unwrap();
+        let totals = table.column_sums();
+        assert_eq!(totals[0].clone().unwrap(), Number::from(3));
+        assert!(totals[1].is_err());
+    }
+
+    #[test]
+    fn test_running_total() {
+        let mut table = Table::new_interpet("'qty|1\n3|\n=a2*2|2\n'x|=1/0\n0.5|").unwrap();
+        let totals = table.running_total(0).unwrap();
+        let expected = ["0", "3", "9", "9", "9.5"]
+            .map(|n| Number::from_str(n).unwrap())
+            .to_vec();
+        assert_eq!(totals, expected);
+        assert!(table.running_total(1).is_err());
+        assert!(matches!(
+            table.running_total(2),
+            Err(TableError::CellOutOfBounds((0, 2)))
+        ));
+    }
+
+    #[test]
+    fn test_transpose_range() {
+        let mut table = Table::new_interpet("1|2|3|\n4|5|=c1*2|\n7|8|9|").unwrap();
+        let transposed = table.transpose_range((0..2, 0..3)).unwrap();
+        assert_eq!((transposed.rows, transposed.cols), (3, 2));
+        let values = (0..3)
+            .map(|row| {
+                (0..2)
+                    .map(|col| transposed[(row, col)].clone().unwrap().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, [["1", "4"], ["2", "5"], ["3", "6"]]);
+
+        let transposed = table.transpose_range((1..3, 3..4)).unwrap();
+        assert_eq!((transposed.rows, transposed.cols), (1, 2));
+        assert_eq!(transposed[(0, 0)].clone().unwrap(), Value::Empty);
+        assert!(matches!(
+            table.transpose_range((0..4, 0..1)),
+            Err(TableError::CellOutOfBounds((3, 0)))
+        ));
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut table = Table::new_interpet("4|'x, y\n=sum(a1:a1, 3)|'say \"hi\"").unwrap();
+        assert_eq!(
+            table.to_csv(),
+            "4,\"x, y\"\n\"=sum(a1:a1, 3)\",\"say \"\"hi\"\"\"\n"
+        );
+        table.run();
+        assert_eq!(table.to_csv(), "4,\"x, y\"\n7,\"say \"\"hi\"\"\"\n");
+    }
+
+    #[test]
+    fn test_tab_delimiter() {
+        let source = "1\t'a|b\t=a1*2\n'x\\\ty\t\t=sum(a1, a1)";
+        let mut table = Table::new_interpet_with_delimiter(source, '\t').unwrap();
+        assert_eq!((table.rows(), table.cols()), (2, 3));
+        table.run();
+        assert_eq!(table.get_value(0, 1).unwrap(), Value::Text("a|b".into()));
+        assert_eq!(
+            table.get_value(0, 2).unwrap(),
+            Value::Number(Number::from(2))
+        );
+        assert_eq!(table.get_value(1, 0).unwrap(), Value::Text("x\ty".into()));
+        assert_eq!(table.get_value(1, 1).unwrap(), Value::Empty);
+        assert_eq!(
+            table.get_value(1, 2).unwrap(),
+            Value::Number(Number::from(2))
+        );
+        assert_eq!(split_row("a\\→b→c", '→'), ["a→b", "c"]);
+    }
+
+    #[test]
+    fn test_escaped_delimiter() {
+        assert_eq!(split_row("1|a\\|b|2", '|'), ["1", "a|b", "2"]);
+        assert_eq!(split_row("a\\\\|b", '|'), ["a\\", "b"]);
+        assert_eq!(split_row("a\\b|", '|'), ["a\\b", ""]);
+        assert_eq!(split_row("a\\|b", '|'), ["a|b"]);
+        assert_eq!(split_row("a|b\\", '|'), ["a", "b\\"]);
+        assert_eq!(split_row("\\||", '|'), ["|", ""]);
+
+        let mut table = Table::new_interpet("'a\\|b|1\n'x\\|y\\|z|=b1+1").unwrap();
+        assert_eq!(table.cols(), 2);
+        table.run();
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Text("a|b".to_string())
+        );
+        assert_eq!(
+            table.get_value(1, 0).unwrap(),
+            Value::Text("x|y|z".to_string())
+        );
+        assert_eq!(
+            table.get_value(1, 1).unwrap(),
+            Value::Number(Number::from(2))
+        );
+
+        let mut table = Table::new_interpet("=\"a\\|b\"|=type(a1)").unwrap();
+        assert_eq!(table.cols(), 2);
+        table.run();
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Text("a|b".to_string())
+        );
+    }
+
+    #[test]
+    fn test_variables() {
+        let mut table = Table::new_interpet("2|=price * quantity|=Price + a1|=rate").unwrap();
+        table.set_variables(HashMap::from([
+            ("price".to_string(), Number::from(3)),
+            ("QUANTITY".to_string(), Number::from(4)),
+        ]));
+        table.run();
+        assert_eq!(
+            table.to_string(),
+            "2|12|5|Runtime Error: Unknown name `rate`|\n"
+        );
+
+        table.set_variables([("a1".to_string(), Number::from(10))]);
+        table.run();
+        assert_eq!(
+            table.get_value(0, 2).unwrap_err().to_string(),
+            "Runtime Error: Unknown name `price`"
+        );
+        table.set_cell(0, 1, "=a1 * 2").unwrap();
+        table.run();
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Number(Number::from(20))
+        );
+    }
+
+    #[test]
+    fn test_empty_cells_are_compact() {
+        let mut table = Table::new_interpet("1||'x\n|=a1*20|").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "1||x|\n|20||\n");
+        assert_eq!(table.to_aligned_string(), "1|  |x|\n |20| |\n");
+    }
+
+    #[test]
+    fn test_evaluate_all() {
+        let mut table = Table::new_interpet("1|'a|true\n=a1*2|=b1|=a3\n||=1+").unwrap();
+        let values = table.evaluate_all();
+        assert_eq!((values.rows, values.cols), (3, 3));
+        let number = |n: u32| Value::Number(Number::from(n));
+        assert_eq!(values[(0, 0)].clone().unwrap(), number(1));
+        assert_eq!(
+            values[(0, 1)].clone().unwrap(),
+            Value::Text("a".to_string())
+        );
+        assert_eq!(values[(0, 2)].clone().unwrap(), Value::Bool(true));
+        assert_eq!(values[(1, 0)].clone().unwrap(), number(2));
+        assert_eq!(
+            values[(1, 1)].clone().unwrap(),
+            Value::Text("a".to_string())
+        );
+        assert_eq!(values[(1, 2)].clone().unwrap(), Value::Empty);
+        assert_eq!(values[(2, 0)].clone().unwrap(), Value::Empty);
+
+        assert_eq!(table.dependencies_of(1, 0), [(0, 0)]);
+        assert!(matches!(
+            values[(2, 2)],
+            Err(TableError::ErrorConstructingAst(_))
+        ));
+    }
+
+    #[test]
+    fn test_run_deep_chain_in_dependency_order() {
+        let depth = 5_000;
+        let source = (1..depth)
+            .map(|row| format!("=a{}+1", row + 1))
+            .chain(["0".to_string()])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut table = Table::new_interpet(&source).unwrap();
+        let report = table.run_with_report();
+        assert_eq!(report.evaluations, depth - 1);
+        assert!(report.evaluations_per_cell.values().all(|&n| n == 1));
+        assert_eq!(
+            table.get_number(0, 0).unwrap(),
+            Number::from(depth as u64 - 1)
+        );
+
+        let mut table = Table::new_interpet("=a2|1\n=a1|=b1*2").unwrap();
+        table.run();
+        assert!(matches!(
+            table.get_value(0, 0),
+            Err(TableError::RecursiveCellExpr(_))
+        ));
+        assert_eq!(table.get_number(1, 1).unwrap(), Number::from(2));
+    }
+
+    #[test]
+    fn test_run_report_diamond() {
+        let mut table = Table::new_interpet("=1|=a1+1|=a1*2|=b1+c1").unwrap();
+        let report = table.run_with_report();
+        assert_eq!(table.to_string(), "1|2|2|4|\n");
+        assert_eq!(report.evaluations, 4);
+        assert_eq!(report.evaluations_per_cell[&(0, 0)], 1);
+        assert_eq!(report.evaluations_per_cell[&(0, 3)], 1);
+
+        let report = table.run_with_report();
+        assert_eq!(report, RunReport::default());
+    }
+
+    #[test]
+    fn test_large_and_small() {
+        let mut table = Table::new_interpet(
+            "4|=large(a1:a5, 1)|\n9|=large(a1:a5, 2)|=small(a1:a5, 1)\n1|=small(a1:a5, 5)|=large(a1:a5, 6)\n9|=large(a1:a5, 0)|\n2||",
+        )
+        .unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        assert_eq!(value(0, 1).unwrap(), Value::Number(Number::from(9)));
+        assert_eq!(value(1, 1).unwrap(), Value::Number(Number::from(9)));
+        assert_eq!(value(1, 2).unwrap(), Value::Number(Number::from(1)));
+        assert_eq!(value(2, 1).unwrap(), Value::Number(Number::from(9)));
+        assert!(value(2, 2)
+            .unwrap_err()
+            .to_string()
+            .contains("Position 6 is out of range for 5 values"));
+        assert!(value(3, 1).is_err());
+    }
+
+    #[test]
+    fn test_lazy_parsing() {
+        let source = "=b1*2|=sum(c1:c2)|1\n=a1+|'x|2";
+        let mut table = Table::new_interpet_lazy(source).unwrap();
+        assert_eq!(table.to_string(), "=b1*2|=sum(c1:c2)|1|\n=a1+|'x|2|\n");
+        assert_eq!(table.dependencies_of(0, 0), [(0, 1)]);
+        assert_eq!(
+            table.get_value(0, 2).unwrap(),
+            Value::Number(Number::from(1))
+        );
+        assert!(table.get_value(0, 0).is_err());
+
+        assert_eq!(
+            table.evaluate_cell(0, 0).unwrap(),
+            Value::Number(Number::from(6))
+        );
+        // a1, b1, c1 and c2 were parsed; the broken formula in a2 and b2 were not.
+        let report = table.run_with_report();
+        assert_eq!(report.lazy_parses, 2);
+        assert_eq!(report.evaluations, 0);
+        assert!(matches!(
+            table.grid[(1, 0)],
+            Err(TableError::ErrorConstructingAst(_))
+        ));
+
+        let mut eager = Table::new_interpet(source);
+        assert!(eager.is_ok());
+        eager.as_mut().unwrap().run();
+        assert_eq!(table.to_string(), eager.unwrap().to_string());
+
+        let mut table = Table::new_interpet_lazy(source).unwrap();
+        let report = table.run_with_report();
+        assert_eq!(report.lazy_parses, 6);
+        assert_eq!(report.evaluations, 2);
+    }
+
+    #[test]
+    fn test_if() {
+        let mut table = Table::new_interpet(
+            "2|0|true|=if(a1, b1 + 10, 1 + )|=if(b1, 1, a1 * 3)|=if(c1, \"yes\", \"no\")\n=if(a1 - 2, 1, a2)|=if(1, 2)|'x|=if(c2, 1, 2)|=if(b1 + 0.5, 7, 8)|=if(a1:b1, 1, 2)",
+        )
+        .unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        assert!(value(0, 3).is_err());
+        assert_eq!(value(0, 4).unwrap(), Value::Number(Number::from(6)));
+        assert_eq!(value(0, 5).unwrap(), Value::Text("yes".to_string()));
+        assert!(value(1, 0).is_err());
+        assert!(matches!(
+            value(1, 1),
+            Err(TableError::RuntimeError(msg)) if msg.starts_with("Expected (condition, then, else)")
+        ));
+        assert!(value(1, 3).is_err());
+        assert_eq!(value(1, 4).unwrap(), Value::Number(Number::from(7)));
+        assert!(matches!(value(1, 5), Err(TableError::MultipleCellReturn)));
+
+        let mut table =
+            Table::new_interpet("=if(1, 5, 1/0)|=if(0, 1/0, 6)|=if(true, a1, b1)").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "5|6|5|\n");
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let mut table = Table::new_interpet(
+            "2|3|=a1<b1|=a1>b1|=a1<=2|=b1>=4|=a1*1.5=b1|=a1<>2\n=if(b1 > a1, b1, a1)|=sum(a1:b1) >= 5|=1 + (a1 = 2)|=-1 < 0|=a1<b1<1|=\"a\" = 1||",
+        )
+        .unwrap();
+        table.run();
+        assert_eq!(
+            table.to_string().lines().next().unwrap(),
+            "2|3|1|0|1|0|1|0|"
+        );
+        let value = |col| table.get_value(1, col);
+        assert_eq!(value(0).unwrap(), Value::Number(Number::from(3)));
+        assert_eq!(value(1).unwrap(), Value::Number(Number::from(1)));
+        assert_eq!(value(2).unwrap(), Value::Number(Number::from(2)));
+        assert_eq!(value(3).unwrap(), Value::Number(Number::from(1)));
+        assert_eq!(value(4).unwrap(), Value::Number(Number::from(0)));
+        assert!(value(5).is_err());
+    }
+
+    #[test]
+    fn test_concatenation() {
+        let source = "Total|price|2.5|true\n=a1 & b1|=a1 & \" \" & c1|=a1 & c1 + 1|=b1 & d1";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(table.get_value(1, 0).unwrap(), text("Totalprice"));
+        assert_eq!(table.get_value(1, 1).unwrap(), text("Total 2.5"));
+        assert_eq!(table.get_value(1, 2).unwrap(), text("Total3.5"));
+        assert_eq!(table.get_value(1, 3).unwrap(), text("pricetrue"));
+    }
+
+    #[test]
+    fn test_choose() {
+        let mut table = Table::new_interpet(
+            "1|2|3|=choose(2, a1, b1, c1)\n=1+|'x|0|=choose(c2 + 1, a1 * 10, a2)\n|||=choose(4, a1, b1, c1)",
+        )
+        .unwrap();
+        table.run();
+        let value = |row, col| table.get_value(row, col);
+        assert_eq!(value(0, 3).unwrap(), Value::Number(Number::from(2)));
+        assert_eq!(value(1, 3).unwrap(), Value::Number(Number::from(10)));
+        assert!(value(2, 3)
+            .unwrap_err()
+            .to_string()
+            .contains("Choice 4 is out of range for 3 options"));
+
+        let mut table = Table::new_interpet("=choose(2, a1, 7)|=choose(0, 1)").unwrap();
+        table.run();
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Number(Number::from(7))
+        );
+        assert!(table.get_value(0, 1).is_err());
+    }
+
+    #[test]
+    fn test_run_iterative_converges() {
+        let source = "=b1/2 + 1|=a1/2|=a1+b1";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        assert!(table.get_value(0, 0).is_err());
+
+        let mut table = Table::new_interpet(source).unwrap();
+        let tolerance = Number::from_str("0.000001").unwrap();
+        let iterations = table.run_iterative(100, tolerance.clone()).unwrap();
+        assert!(iterations > 1 && iterations < 100);
+        let value = |row, col| match table.get_value(row, col).unwrap() {
+            Value::Number(d) => d.round(4),
+            other => panic!("expected a number, got {other:?}"),
+        };
+        assert_eq!(value(0, 0), Number::from_str("1.3333").unwrap());
+        assert_eq!(value(0, 1), Number::from_str("0.6667").unwrap());
+        assert_eq!(value(0, 2), Number::from_str("2.0000").unwrap());
+
+        let mut table = Table::new_interpet("=a1+1").unwrap();
+        assert!(table.run_iterative(10, tolerance).is_err());
+        assert_eq!(
+            table.get_value(0, 0).unwrap(),
+            Value::Number(Number::from(10))
+        );
+    }
+
+    #[test]
+    fn test_distinct_values() {
+        let mut table = Table::new_interpet("3|1\n1|'x\n=a1-1|3.0\n|2").unwrap();
+        let values = table
+            .distinct_values((0..4, 0..2))
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(values, ["3", "1", "2"]);
+        assert!(table.distinct_values((1..2, 1..2)).unwrap().is_empty());
+        assert!(matches!(
+            table.distinct_values((0..5, 0..2)),
+            Err(TableError::CellOutOfBounds((4, 1)))
+        ));
+    }
+
+    #[test]
+    fn test_abs() {
+        let source = "-2.5|3|0\n=abs(a1)|=abs(b1)|=abs(c1)\n=abs(a1:a3)|=abs(a1, b1)|=abs(-b1 * 2)";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let number = |n: &str| Value::Number(Number::from_str(n).unwrap());
+        assert_eq!(table.get_value(1, 0).unwrap(), number("2.5"));
+        assert_eq!(table.get_value(1, 1).unwrap(), number("3"));
+        assert_eq!(table.get_value(1, 2).unwrap(), number("0"));
+        for col in [0, 1] {
+            assert!(matches!(
+                table.get_value(2, col),
+                Err(TableError::RuntimeError(_))
+            ));
+        }
+        assert_eq!(table.get_value(2, 2).unwrap(), number("6"));
+    }
+
+    #[test]
+    fn test_round() {
+        let source = "1.25|1.24|-1.25|-1.24\n=round(a1, 1)|=round(b1, 1)|=round(c1, 1)|=round(d1, 1)\n=round(a1)|=round(a1, 0.5)|=round(a1, 1, 2)|=round(1275, -2)";
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        let number = |n: &str| Value::Number(Number::from_str(n).unwrap());
+        for (col, expected) in ["1.3", "1.2", "-1.3", "-1.2"].into_iter().enumerate() {
+            assert_eq!(table.get_value(1, col).unwrap(), number(expected));
+        }
+        for col in 0..4 {
+            assert!(matches!(
+                table.get_value(2, col),
+                Err(TableError::RuntimeError(_))
+            ));
+        }
+        assert!(matches!(
+            table.get_value(2, 3),
+            Err(TableError::RuntimeError(message)) if message.starts_with("Expected whole number of digits")
+        ));
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let mut table = Table::new_interpet("=1/3|=1/8|=round(0.125, 2)|=round(2.5, 0)").unwrap();
+        table.set_division_scale(Some(2));
+        table.run();
+        assert_eq!(table.to_string(), "0.33|0.13|0.13|3|\n");
+
+        table.set_rounding_mode(RoundingMode::HalfEven);
+        table.run();
+        assert_eq!(table.to_string(), "0.33|0.12|0.12|2|\n");
+
+        table.set_rounding_mode(RoundingMode::Down);
+        table.set_division_scale(None);
+        table.run();
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Number(Number::from_str("0.125").unwrap())
+        );
+        assert_eq!(
+            table.get_value(0, 2).unwrap(),
+            Value::Number(Number::from_str("0.12").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_floor_division() {
+        let mut table =
+            Table::new_interpet("=7 // 2|=-7 // 2|=7 / 2|=1 + 9 // 2 * 2|=7 // 0|=7 / 0").unwrap();
+        table.run();
+        let number = |n: &str| Value::Number(Number::from_str(n).unwrap());
+        assert_eq!(table.get_value(0, 0).unwrap(), number("3"));
+        assert_eq!(table.get_value(0, 1).unwrap(), number("-4"));
+        assert_eq!(table.get_value(0, 2).unwrap(), number("3.5"));
+        assert_eq!(table.get_value(0, 3).unwrap(), number("9"));
+        for col in [4, 5] {
+            assert!(matches!(
+                table.get_value(0, col),
+                Err(TableError::DivisionByZero)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_out_of_bounds_reference() {
+        let mut table = Table::new_interpet("1|=z99\n=d1 + a1|=b1").unwrap();
+        table.run();
+        assert!(matches!(
+            table.get_value(0, 1),
+            Err(TableError::CellOutOfBounds((98, 25)))
+        ));
+        assert!(matches!(
+            table.get_value(1, 0),
+            Err(TableError::CellOutOfBounds((0, 3)))
+        ));
+        assert!(matches!(
+            table.evaluate_cell_pure(0, 1),
+            Err(TableError::CellOutOfBounds((98, 25)))
+        ));
+        assert!(table
+            .to_string()
+            .starts_with("1|Reference to a cell outside the table"));
+
+        let mut table = Table::new_interpet("1|=z99+1|=sum(a1:z99)|=mean(z99)").unwrap();
+        table.run();
+        for (col, coord) in [(1, (98, 25)), (2, (1, 0)), (3, (98, 25))] {
+            assert!(matches!(
+                table.get_value(0, col),
+                Err(TableError::CellOutOfBounds(c)) if c == coord
+            ));
+        }
+        let outside =
+            |err: Option<TableError>| matches!(err, Some(TableError::CellOutOfBounds((5, 0))));
+        assert!(outside(table.get_value(5, 0).err()));
+        assert!(outside(table.set_cell(5, 0, "1").err()));
+        assert!(outside(table.label_cell(5, 0, "x").err()));
+        assert!(outside(table.set_override(5, 0, Value::Empty).err()));
+        let seek = table.goal_seek((0, 1), Number::from(1), (5, 0), 10, Number::from(0));
+        assert!(outside(seek.err()));
+    }
+
+    #[test]
+    fn test_range_past_last_row() {
+        let mut table =
+            Table::new_interpet("1|=sum(a1:a5)\n2|=count(a2:a9)\n3|=sum(c1:d2)").unwrap();
+        table.run();
+        assert!(table.get_value(0, 1).is_err());
+        assert!(table.get_value(1, 1).is_err());
+
+        table.set_clamp_ranges(true);
+        table.run();
+        let number = |n: u32| Value::Number(Number::from(n));
+        assert_eq!(table.get_value(0, 1).unwrap(), number(6));
+        assert_eq!(table.get_value(1, 1).unwrap(), number(2));
+        assert_eq!(table.get_value(2, 1).unwrap(), number(0));
+
+        table.insert_row(3);
+        table.set_cell(3, 0, "4").unwrap();
+        table.run();
+        assert_eq!(table.get_value(0, 1).unwrap(), number(10));
+    }
+
+    #[cfg(feature = "fast-float")]
+    #[test]
+    fn test_fast_float_rejects_non_finite() {
+        let mut table = Table::new_interpet("inf|NaN|1e300|=c1*c1|=d1*0|=1e999|=-c1*1e10").unwrap();
+        table.run();
+        assert_eq!(table.get_value(0, 0).unwrap(), Value::Text("inf".into()));
+        assert_eq!(table.get_value(0, 1).unwrap(), Value::Text("NaN".into()));
+        for col in 3..=6 {
+            assert!(table.get_value(0, col).is_err(), "{col}");
+        }
+    }
+
+    #[cfg(not(feature = "fast-float"))]
+    #[test]
+    fn test_precision_budget() {
+        use std::num::NonZeroU64;
+
+        let source = "1.0000001|=a1*a1|=b1*b1|=c1*c1|=d1*d1|=e1*e1|=f1*f1";
+        let digits = |table: &Table<Expr>| match table.get_value(0, 6) {
+            Ok(Value::Number(d)) => d.digits(),
+            other => panic!("expected a number, got {other:?}"),
+        };
+        let mut table = Table::new_interpet(source).unwrap();
+        table.run();
+        assert_eq!(digits(&table), 449);
+
+        let budget = |strict| {
+            Some(PrecisionBudget {
+                max_digits: NonZeroU64::new(20).unwrap(),
+                strict,
+            })
+        };
+        table.set_precision_budget(budget(false));
+        table.run();
+        assert!(digits(&table) <= 20);
+        assert_eq!(
+            table.get_value(0, 6).unwrap().to_string(),
+            "1.0000064000201600409"
+        );
+
+        table.set_precision_budget(budget(true));
+        table.run();
+        assert!(table.get_value(0, 1).is_ok());
+        assert!(matches!(
+            table.get_value(0, 2),
+            Err(TableError::RuntimeError(msg)) if msg.contains("does not fit in 20 significant digits")
+        ));
+    }
+
+    #[test]
+    fn test_text_function() {
+        let mut table = Table::new_interpet(
+            "1234.5|=text(a1, \"0.00\")|=text(a1 * 1000, \"#,##0\")|=text(a1, b1)",
+        )
+        .unwrap();
+        table.run();
+        assert_eq!(
+            table.get_value(0, 1).unwrap(),
+            Value::Text("1234.50".to_string())
+        );
+        assert_eq!(
+            table.get_value(0, 2).unwrap(),
+            Value::Text("1,234,500".to_string())
+        );
+        assert!(table.get_value(0, 3).is_err());
+    }
+
+    #[test]
+    fn test_sheet_relative_reference() {
+        let mut table = Table::new_interpet("2|=.A1 * 3|=sum(.a1:.b1)").unwrap();
+        table.run();
+        assert_eq!(table.to_string(), "2|6|8|\n");
+    }
+
+    #[test]
+    fn test_unused_cells() {
+        let mut table = Table::new_interpet("1|2|99\n=a1+b1||'note\n=sum(a2:a2)|=b1*2|").unwrap();
+        assert_eq!(table.unused_cells(&[]), [(0, 2), (1, 2), (2, 0), (2, 1)]);
+        assert_eq!(table.unused_cells(&[(2, 0), (2, 1)]), [(0, 2), (1, 2)]);
+        table.label_cell(1, 2, "note").unwrap();
+        assert_eq!(table.unused_cells(&[(2, 0), (2, 1)]), [(0, 2)]);
+    }
+
+    #[test]
+    fn test_evaluate_cell_pure() {
+        let source = "2|=a1*3|=b1+a1\n=sum(a1:c1)|=a2/2|=c2\n=a1:a2|=b3|";
+        let table = Table::new_interpet(source).unwrap();
+        let shared = &table;
+        let pure = std::thread::scope(|scope| {
+            let handles = [(0, 2), (1, 0), (1, 1)]
+                .map(|(row, col)| scope.spawn(move || shared.evaluate_cell_pure(row, col)));
+            handles.map(|handle| handle.join().unwrap().unwrap())
+        });
+        assert!(table.to_string().contains("=a1*3"));
+
+        let mut cached = table.clone();
+        cached.run();
+        for (value, (row, col)) in pure.into_iter().zip([(0, 2), (1, 0), (1, 1)]) {
+            assert_eq!(value, cached.get_value(row, col).unwrap());
+        }
+        assert!(matches!(
+            table.evaluate_cell_pure(1, 2),
+            Err(TableError::RecursiveCellExpr(path)) if path == [(1, 2), (1, 2)]
+        ));
+        assert!(matches!(
+            table.evaluate_cell_pure(2, 0),
+            Err(TableError::MultipleCellReturn)
+        ));
+        assert_eq!(table.evaluate_cell_pure(2, 2).unwrap(), Value::Empty);
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        let table = Table::new_interpet("=b1|=c1|5\n|=a1+c2|=sum(a1:b1)").unwrap();
+        assert_eq!(
+            table.evaluation_order().unwrap(),
+            [(0, 2), (0, 1), (0, 0), (1, 2), (1, 1)]
+        );
+
+        let table = Table::new_interpet("=b1|=a1").unwrap();
+        assert!(matches!(
+            table.evaluation_order(),
+            Err(TableError::RecursiveCellExpr(_))
+        ));
+    }
+
+    #[test]
+    fn test_long_chain_on_demand() {
+        let depth = 50;
+        let source = (1..depth)
+            .map(|row| format!("=a{} + a{}", row + 1, row + 1))
+            .chain(["1".to_string()])
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut table = Table::new_interpet(&source).unwrap();
+        let expected = (1..depth).fold(Number::from(1), |n, _| n.clone() + n);
+        assert_eq!(table.evaluate_cell(0, 0).unwrap(), Value::Number(expected));
+        assert_eq!(table.report.evaluations, depth - 1);
+
+        let table = Table::new_interpet("=b1 + b1|=c1 * 2|3").unwrap();
+        assert_eq!(
+            table.evaluate_cell_pure(0, 0).unwrap(),
+            Value::Number(Number::from(12))
+        );
+    }
+
+    #[test]
+    fn test_cycle_path() {
+        let mut table = Table::new_interpet("=b1|=a1|=b1").unwrap();
+        table.run();
+        assert!(matches!(
+            table.get_value(0, 0),
+            Err(TableError::RecursiveCellExpr(path)) if path == [(0, 0), (0, 1), (0, 0)]
+        ));
+        assert_eq!(
+            table.get_value(0, 0).unwrap_err().to_string(),
+            "Recursive Cell at: a1 -> b1 -> a1"
+        );
+        assert!(matches!(
+            table.evaluate_cell_pure(0, 2),
+            Err(TableError::RecursiveCellExpr(path)) if path == [(0, 1), (0, 0), (0, 1)]
+        ));
+    }
+
+    #[test]
+    fn test_validate_acyclic() {
+        let table = Table::new_interpet("1|=a1+1|=b1*2\n=sum(a1:c1)|=a2|=b2+c1").unwrap();
+        assert!(table.validate_acyclic().is_ok());
+
+        let table = Table::new_interpet("1|=c1+1|=b2*2\n=sum(a1:a1)|=b1|3").unwrap();
+        assert!(matches!(
+            table.validate_acyclic(),
+            Err(TableError::RecursiveCellExpr(path)) if path == [(0, 1), (0, 2), (1, 1), (0, 1)]
+        ));
+
+        let table = Table::new_interpet("=a1").unwrap();
+        assert!(matches!(
+            table.validate_acyclic(),
+            Err(TableError::RecursiveCellExpr(path)) if path == [(0, 0), (0, 0)]
+        ));
+    }
+}
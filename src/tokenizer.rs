@@ -1,19 +1,64 @@
 use super::error::*;
-use bigdecimal::BigDecimal;
-use std::str::FromStr;
+use crate::number::Number;
+use alloc::{format, string::String, string::ToString, vec::Vec};
+use core::str::FromStr;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
-    Number(BigDecimal),
-    CellRef((usize, usize)),
-    CellRange((std::ops::Range<usize>, std::ops::Range<usize>)),
+    Number(Number),
+    Bool(bool),
+    /// A string literal such as `"0.00"`; `""` inside it is a literal quote.
+    Str(String),
+    /// An identifier that is neither a builtin nor a cell reference, resolved
+    /// against the variables of the `EvalContext`.
+    Name(String),
+    CellRef(CellRef),
+    /// `@name`, a cell labelled with `Table::label_cell`. Its coordinate is only
+    /// known at evaluation time, so it is not among an expression's `references`.
+    Label(String),
+    CellRange(CellRange),
     Comma,
     Sum,
     Mean,
+    MaxIfs,
+    MinIfs,
+    Large,
+    Small,
+    Round,
+    Text,
+    Choose,
+    SumProduct,
+    Year,
+    Month,
+    Day,
+    Weekday,
+    Prev,
+    Min,
+    Max,
+    Count,
+    Product,
+    Type,
+    If,
+    Abs,
     Plus,
     Slash,
+    /// `//`, division rounded down to a whole number.
+    SlashSlash,
     Minus,
     Star,
+    /// `^`, exponentiation; binds tighter than `*` and groups to the right.
+    Caret,
+    /// `&`, joining its operands as text; binds more loosely than `+` and `-`.
+    Ampersand,
+    /// Comparisons, giving 1 when they hold and 0 otherwise.
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    /// Postfix `%`, dividing the preceding expression by 100.
+    Percent,
     LeftParen,
     RightParen,
 }
@@ -23,17 +68,237 @@ impl Token {
         matches!(self, Self::Number(..))
     }
 
+    pub fn is_bool(&self) -> bool {
+        matches!(self, Self::Bool(..))
+    }
+
+    pub fn is_str(&self) -> bool {
+        matches!(self, Self::Str(..))
+    }
+
+    pub fn is_name(&self) -> bool {
+        matches!(self, Self::Name(..))
+    }
+
     pub fn is_cell_ref(&self) -> bool {
         matches!(self, Self::CellRef(..))
     }
 
+    pub fn is_label(&self) -> bool {
+        matches!(self, Self::Label(..))
+    }
+
     pub fn is_cell_range(&self) -> bool {
         matches!(self, Self::CellRange(..))
     }
 
     pub fn is_builtin_fn(&self) -> bool {
         use Token::*;
-        matches!(self, Sum | Mean)
+        matches!(
+            self,
+            Sum | Mean
+                | MaxIfs
+                | MinIfs
+                | Large
+                | Small
+                | Round
+                | Text
+                | Choose
+                | SumProduct
+                | Year
+                | Month
+                | Day
+                | Weekday
+                | Prev
+                | Min
+                | Max
+                | Count
+                | Product
+                | Type
+                | If
+                | Abs
+        )
+    }
+}
+
+/// Formats a zero-based `(row, col)` coordinate in A1 notation, e.g. `(1, 27)` is `ab2`.
+pub fn cell_name(row: usize, col: usize) -> String {
+    let mut letters = Vec::new();
+    let mut col = col + 1;
+    while col > 0 {
+        let rem = (col - 1) % 26;
+        letters.push((b'a' + rem as u8) as char);
+        col = (col - 1) / 26;
+    }
+    let mut name = letters.into_iter().rev().collect::<String>();
+    name.push_str(&format!("{}", row + 1));
+    name
+}
+
+/// A reference to a single cell, such as `a1`. A `$` before the column or the
+/// row (`$a$1`, `$a1`, `a$1`) marks that part absolute. The flags are kept so
+/// the formula prints as written, but evaluation does not look at them yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRef {
+    pub row: usize,
+    pub col: usize,
+    pub row_absolute: bool,
+    pub col_absolute: bool,
+}
+
+impl CellRef {
+    pub fn coord(&self) -> (usize, usize) {
+        (self.row, self.col)
+    }
+}
+
+impl From<(usize, usize)> for CellRef {
+    /// A relative reference to `(row, col)`.
+    fn from((row, col): (usize, usize)) -> Self {
+        Self {
+            row,
+            col,
+            row_absolute: false,
+            col_absolute: false,
+        }
+    }
+}
+
+impl core::fmt::Display for CellRef {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let name = cell_name(self.row, self.col);
+        let (column, row) = name.split_at(name.find(|c: char| c.is_ascii_digit()).unwrap_or(0));
+        let dollar = |absolute| if absolute { "$" } else { "" };
+        write!(
+            f,
+            "{}{column}{}{row}",
+            dollar(self.col_absolute),
+            dollar(self.row_absolute)
+        )
+    }
+}
+
+/// A rectangular range such as `a1:b2`, held by its top-left and bottom-right
+/// corners. Each corner keeps the `$` flags written on it, as `CellRef` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellRange {
+    pub start: CellRef,
+    pub end: CellRef,
+}
+
+impl CellRange {
+    /// The range spanned by two opposite corners in either order. Each row and
+    /// column bound keeps the flag of the endpoint it came from, so `b$2:$a1`
+    /// is `$a1:b$2`.
+    pub fn new(first: CellRef, second: CellRef) -> Self {
+        let (top, bottom) = if first.row <= second.row {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let (left, right) = if first.col <= second.col {
+            (first, second)
+        } else {
+            (second, first)
+        };
+        let corner = |rows: CellRef, cols: CellRef| CellRef {
+            row: rows.row,
+            col: cols.col,
+            row_absolute: rows.row_absolute,
+            col_absolute: cols.col_absolute,
+        };
+        Self {
+            start: corner(top, left),
+            end: corner(bottom, right),
+        }
+    }
+
+    pub fn rows(&self) -> core::ops::Range<usize> {
+        self.start.row..self.end.row + 1
+    }
+
+    pub fn cols(&self) -> core::ops::Range<usize> {
+        self.start.col..self.end.col + 1
+    }
+}
+
+impl From<(core::ops::Range<usize>, core::ops::Range<usize>)> for CellRange {
+    /// A relative range over the non-empty `(rows, cols)`.
+    fn from((rows, cols): (core::ops::Range<usize>, core::ops::Range<usize>)) -> Self {
+        Self {
+            start: (rows.start, cols.start).into(),
+            end: (rows.end - 1, cols.end - 1).into(),
+        }
+    }
+}
+
+impl core::fmt::Display for CellRange {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+/// Whether `identifier` has the shape of an A1 reference: letters, then digits.
+fn is_cell_name(identifier: &str) -> bool {
+    let letters = identifier
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .count();
+    let digits = &identifier[letters..];
+    letters > 0 && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+impl core::fmt::Display for Token {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use Token::*;
+        match self {
+            Number(d) => write!(f, "{d}"),
+            Bool(b) => write!(f, "{b}"),
+            Str(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Name(name) => write!(f, "{name}"),
+            CellRef(cell) => write!(f, "{cell}"),
+            Label(name) => write!(f, "@{name}"),
+            CellRange(range) => write!(f, "{range}"),
+            Comma => write!(f, ","),
+            Sum => write!(f, "sum"),
+            Mean => write!(f, "mean"),
+            MaxIfs => write!(f, "maxifs"),
+            MinIfs => write!(f, "minifs"),
+            Large => write!(f, "large"),
+            Small => write!(f, "small"),
+            Round => write!(f, "round"),
+            Text => write!(f, "text"),
+            Choose => write!(f, "choose"),
+            SumProduct => write!(f, "sumproduct"),
+            Year => write!(f, "year"),
+            Month => write!(f, "month"),
+            Day => write!(f, "day"),
+            Weekday => write!(f, "weekday"),
+            Prev => write!(f, "prev"),
+            Min => write!(f, "min"),
+            Max => write!(f, "max"),
+            Count => write!(f, "count"),
+            Product => write!(f, "product"),
+            Type => write!(f, "type"),
+            If => write!(f, "if"),
+            Abs => write!(f, "abs"),
+            Plus => write!(f, "+"),
+            Slash => write!(f, "/"),
+            SlashSlash => write!(f, "//"),
+            Minus => write!(f, "-"),
+            Star => write!(f, "*"),
+            Caret => write!(f, "^"),
+            Ampersand => write!(f, "&"),
+            Less => write!(f, "<"),
+            LessEqual => write!(f, "<="),
+            Greater => write!(f, ">"),
+            GreaterEqual => write!(f, ">="),
+            Equal => write!(f, "="),
+            NotEqual => write!(f, "<>"),
+            Percent => write!(f, "%"),
+            LeftParen => write!(f, "("),
+            RightParen => write!(f, ")"),
+        }
     }
 }
 
@@ -46,6 +311,12 @@ impl TryFrom<char> for Token {
             '-' => Ok(Minus),
             '/' => Ok(Slash),
             '*' => Ok(Star),
+            '^' => Ok(Caret),
+            '&' => Ok(Ampersand),
+            '<' => Ok(Less),
+            '>' => Ok(Greater),
+            '=' => Ok(Equal),
+            '%' => Ok(Percent),
             '(' => Ok(LeftParen),
             ')' => Ok(RightParen),
             ',' => Ok(Comma),
@@ -58,11 +329,33 @@ impl TryFrom<char> for Token {
 
 pub struct Tokenizer<'a> {
     source: &'a [char],
+    lenient: bool,
+    /// Whether the next token starts an operand: true at the start of the
+    /// input and after an operator, `(` or `,`. Only then is `-5` read as a
+    /// negative number rather than a minus sign.
+    expects_operand: bool,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(source: &'a [char]) -> Self {
-        Self { source }
+        Self {
+            source,
+            lenient: false,
+            expects_operand: true,
+        }
+    }
+
+    /// A tokenizer that also accepts legacy row-first references such as `1A`.
+    ///
+    /// A number is only read as a reference when letters directly follow its
+    /// digits and they are not a numeric suffix, so `1`, `1.5` and `25bp` are
+    /// still numbers while `1A` and `12ab` are cell references.
+    pub fn new_lenient(source: &'a [char]) -> Self {
+        Self {
+            source,
+            lenient: true,
+            expects_operand: true,
+        }
     }
 
     fn at_end(&mut self) -> bool {
@@ -102,7 +395,7 @@ impl<'a> Tokenizer<'a> {
     fn chop(&mut self, n: usize) -> &'a [char] {
         let result = &self.source[..n];
         self.source = &self.source[n..];
-        return result;
+        result
     }
 
     fn strip_left(&mut self) {
@@ -120,9 +413,13 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Parses a number, with a leading `-` when `at_negative_number` holds.
     fn number(&mut self) -> TableResult<Token> {
-        let source = self.chop_while(|c| c.is_numeric());
-        let mut string_num = source.iter().collect::<String>();
+        let mut string_num = String::new();
+        if self.peek_match(|c| c == '-') {
+            string_num.extend(self.chop(1));
+        }
+        string_num.extend(self.chop_while(|c| c.is_numeric()));
 
         if !self.at_end() && self.source[0] == '.' {
             self.chop(1);
@@ -131,87 +428,260 @@ impl<'a> Tokenizer<'a> {
             string_num.extend(chars);
         }
 
-        let decimal = BigDecimal::from_str(&string_num).map_err(|_| {
+        // An exponent is a lone `e` or `E`; a longer run of letters is a suffix.
+        if self.peek_match(|c| c == 'e' || c == 'E')
+            && !self.source.get(1).is_some_and(|c| c.is_alphabetic())
+        {
+            string_num.extend(self.chop(1));
+            if self.peek_match(|c| c == '+' || c == '-') {
+                string_num.extend(self.chop(1));
+            }
+            let digits = self.chop_while_or_else(
+                |c| c.is_ascii_digit(),
+                TableError::InvalidCell(format!("Missing exponent digits in {string_num}")),
+            )?;
+            string_num.extend(digits);
+        }
+
+        let decimal = Number::from_str(&string_num).map_err(|_| {
             TableError::InvalidCell(format!("Could not format {string_num} as a valid number"))
         })?;
+        let decimal = match self.number_suffix()? {
+            Some(divisor) => decimal / Number::from(divisor),
+            None => decimal,
+        };
         Ok(Token::Number(decimal))
     }
 
-    fn parse_cell_reference(&mut self) -> TableResult<(usize, usize)> {
+    /// Consumes a unit directly after a number: `%` (1/100) or `bp` (1/10000).
+    ///
+    /// Any other letters touching the digits are an error rather than a new
+    /// token, so `25x` is rejected instead of being read as `25` and `x`.
+    fn number_suffix(&mut self) -> TableResult<Option<u32>> {
+        if self.peek_match(|c| c == '%') {
+            self.chop(1);
+            return Ok(Some(100));
+        }
+        let n = self.peek_while(|c| c.is_alphabetic());
+        if n == 0 {
+            return Ok(None);
+        }
+        let suffix = self.chop(n).iter().collect::<String>();
+        match suffix.to_ascii_lowercase().as_str() {
+            "bp" => Ok(Some(10_000)),
+            _ => Err(TableError::InvalidCell(format!(
+                "Unknown numeric suffix: {suffix}"
+            ))),
+        }
+    }
+
+    /// Parses `a1`, with an optional `$` before the column and before the row,
+    /// or a lenient row-first `1a`, which cannot be absolute.
+    fn parse_cell_reference(&mut self) -> TableResult<CellRef> {
+        if self.lenient && self.peek_match(|c| c.is_numeric()) {
+            let row = self.parse_row()?;
+            return Ok((row, self.parse_column()?).into());
+        }
+        let col_absolute = self.chop_dollar();
+        let col = self.parse_column()?;
+        let row_absolute = self.chop_dollar();
+        let row = self.parse_row()?;
+        Ok(CellRef {
+            row,
+            col,
+            row_absolute,
+            col_absolute,
+        })
+    }
+
+    /// Consumes a `$` marking the next part of a reference as absolute.
+    fn chop_dollar(&mut self) -> bool {
+        let dollar = self.peek_match(|c| c == '$');
+        if dollar {
+            self.chop(1);
+        }
+        dollar
+    }
+
+    fn parse_column(&mut self) -> TableResult<usize> {
         let column_slice = self.chop_while_or_else(
             |c| c.is_ascii_alphabetic(),
-            TableError::InvalidCell(format!("Could not parse cell reference")),
+            TableError::InvalidCell("Could not parse cell reference".to_string()),
         )?;
 
-        let mut col = 0;
+        let overflow = || TableError::InvalidCell("Column reference is too large".to_string());
+        let mut col: usize = 0;
         let base: usize = 26;
         for (i, c) in column_slice.iter().rev().enumerate() {
             if c.is_ascii_alphabetic() {
                 let col_ref = c.to_ascii_lowercase() as usize - 96;
-                col += col_ref * base.pow(i as u32);
+                let place = u32::try_from(i)
+                    .ok()
+                    .and_then(|i| base.checked_pow(i))
+                    .ok_or_else(overflow)?;
+                col = col_ref
+                    .checked_mul(place)
+                    .and_then(|value| col.checked_add(value))
+                    .ok_or_else(overflow)?;
             }
         }
+        Ok(col - 1)
+    }
 
+    fn parse_row(&mut self) -> TableResult<usize> {
         let row_slice = self.chop_while_or_else(
             |c| c.is_numeric(),
-            TableError::InvalidCell(format!("Could not parse cell reference")),
+            TableError::InvalidCell("Could not parse cell reference".to_string()),
         )?;
 
         let row = row_slice
-            .into_iter()
+            .iter()
             .collect::<String>()
             .parse::<usize>()
-            .map_err(|_| TableError::InvalidCell(format!("Could not parse cell reference")))?;
+            .map_err(|_| TableError::InvalidCell("Could not parse cell reference".to_string()))?;
 
         if row == 0 {
-            return Err(TableError::InvalidCell(format!(
-                "Could not parse cell reference"
-            )));
+            return Err(TableError::InvalidCell(
+                "Could not parse cell reference".to_string(),
+            ));
         }
-        Ok((row - 1, col - 1))
+        Ok(row - 1)
+    }
+
+    /// In lenient mode, whether the digits at the front start a `1A` style reference.
+    fn at_row_first_reference(&self) -> bool {
+        self.lenient && starts_row_first_reference(self.source)
+    }
+
+    /// Whether the input is a `-` that belongs to a number literal: it must be
+    /// directly followed by a digit and come where an operand is expected, so
+    /// `-5` and `(-5)` give a negative number while `a1-5` is a subtraction.
+    /// In lenient mode `-1A` stays a minus sign before a reference.
+    fn at_negative_number(&self) -> bool {
+        self.expects_operand
+            && self.source.len() > 1
+            && self.source[0] == '-'
+            && self.source[1].is_numeric()
+            && !(self.lenient && starts_row_first_reference(&self.source[1..]))
     }
 
+    /// Whether the input starts with `.` and a column, as in `.a1`: a reference
+    /// to the current sheet, which is the only sheet a table has.
+    fn at_sheet_relative_reference(&self) -> bool {
+        self.source.len() > 1 && self.source[0] == '.' && self.source[1].is_ascii_alphabetic()
+    }
+
+    /// Parses a cell reference, or a range when followed by `:`.
+    ///
+    /// Whitespace around the colon is allowed (`a1 : b2`).
+    /// Both range endpoints must be literal cell references (`a1:b2`); computed
+    /// endpoints such as `a1:a(1+2)` are rejected rather than partially tokenized.
     fn cell_reference(&mut self) -> TableResult<Token> {
-        let (row, col) = self.parse_cell_reference()?;
+        let cell = self.parse_cell_reference()?;
 
+        self.strip_left();
         if !self.peek_match(|c| c == ':') {
-            return Ok(Token::CellRef((row, col)));
+            return Ok(Token::CellRef(cell));
         }
 
         self.chop(1);
+        self.strip_left();
+        if self.at_sheet_relative_reference() {
+            self.chop(1);
+        }
 
-        let (next_row, next_col) = self
-            .parse_cell_reference()
-            .map_err(|_| TableError::InvalidCell(format!("Invalid cell range")))?;
+        let next = self.parse_cell_reference().map_err(|_| {
+            TableError::InvalidCell(
+                "Invalid cell range: range endpoints must be cell references".to_string(),
+            )
+        })?;
 
-        Ok(Token::CellRange((
-            std::ops::Range {
-                start: row,
-                end: next_row + 1,
-            },
-            std::ops::Range {
-                start: col,
-                end: next_col + 1,
-            },
-        )))
+        Ok(Token::CellRange(CellRange::new(cell, next)))
     }
 
+    /// Tokenizes an identifier: a builtin, a cell reference (letters followed by
+    /// digits) or otherwise a `Name`. Identifiers are case-insensitive.
     pub fn literal(&mut self) -> TableResult<Token> {
-        let n = self.peek_while(|c| c.is_alphabetic());
-        let res = match self.source[0..n]
+        let n = self.peek_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        let identifier = self.source[0..n]
             .iter()
             .map(|c| c.to_ascii_lowercase())
-            .collect::<String>()
-            .as_ref()
-        {
+            .collect::<String>();
+        let res = match identifier.as_ref() {
             "sum" => Ok(Token::Sum),
-            "mean" => Ok(Token::Mean),
-            _ => return self.cell_reference(),
+            "mean" | "avg" => Ok(Token::Mean),
+            "maxifs" => Ok(Token::MaxIfs),
+            "minifs" => Ok(Token::MinIfs),
+            "large" => Ok(Token::Large),
+            "small" => Ok(Token::Small),
+            "round" => Ok(Token::Round),
+            "text" => Ok(Token::Text),
+            "choose" => Ok(Token::Choose),
+            "sumproduct" => Ok(Token::SumProduct),
+            "year" => Ok(Token::Year),
+            "month" => Ok(Token::Month),
+            "day" => Ok(Token::Day),
+            "weekday" => Ok(Token::Weekday),
+            "prev" => Ok(Token::Prev),
+            "min" => Ok(Token::Min),
+            "max" => Ok(Token::Max),
+            "count" => Ok(Token::Count),
+            "product" => Ok(Token::Product),
+            "type" => Ok(Token::Type),
+            "if" => Ok(Token::If),
+            "abs" => Ok(Token::Abs),
+            "inf" | "infinity" => Err(TableError::InvalidCell(
+                "infinity is not representable".to_string(),
+            )),
+            "nan" => Err(TableError::InvalidCell(
+                "NaN is not representable".to_string(),
+            )),
+            "true" => Ok(Token::Bool(true)),
+            "false" => Ok(Token::Bool(false)),
+            _ if is_cell_name(&identifier) => return self.cell_reference(),
+            _ if self.source.get(n) == Some(&'$')
+                && identifier.chars().all(|c| c.is_ascii_alphabetic()) =>
+            {
+                return self.cell_reference()
+            }
+            _ => Ok(Token::Name(identifier)),
         };
         self.chop(n);
         res
     }
 
+    fn label(&mut self) -> TableResult<Token> {
+        self.chop(1);
+        let name = self
+            .chop_while_or_else(
+                |c| c.is_ascii_alphanumeric() || c == '_',
+                TableError::InvalidCell("Expected a label name after '@'".to_string()),
+            )?
+            .iter()
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        Ok(Token::Label(name))
+    }
+
+    fn string(&mut self) -> TableResult<Token> {
+        self.chop(1);
+        let mut s = String::new();
+        loop {
+            s.extend(self.chop_while(|c| c != '"'));
+            if self.at_end() {
+                return Err(TableError::InvalidCell(
+                    "Unterminated string literal".to_string(),
+                ));
+            }
+            self.chop(1);
+            if !self.peek_match(|c| c == '"') {
+                return Ok(Token::Str(s));
+            }
+            self.chop(1);
+            s.push('"');
+        }
+    }
+
     fn next_token(&mut self) -> Option<TableResult<Token>> {
         self.strip_left();
         if self.at_end() {
@@ -219,8 +689,33 @@ impl<'a> Tokenizer<'a> {
         }
 
         let token = match self.source[0] {
+            '"' => self.string(),
+            '@' => self.label(),
+            '/' if self.source.get(1) == Some(&'/') => {
+                self.chop(2);
+                Ok(Token::SlashSlash)
+            }
+            '<' if self.source.get(1) == Some(&'=') => {
+                self.chop(2);
+                Ok(Token::LessEqual)
+            }
+            '<' if self.source.get(1) == Some(&'>') => {
+                self.chop(2);
+                Ok(Token::NotEqual)
+            }
+            '>' if self.source.get(1) == Some(&'=') => {
+                self.chop(2);
+                Ok(Token::GreaterEqual)
+            }
+            '.' if self.at_sheet_relative_reference() => {
+                self.chop(1);
+                self.cell_reference()
+            }
+            '$' => self.cell_reference(),
             c if c.is_ascii_alphabetic() => self.literal(),
+            c if c.is_numeric() && self.at_row_first_reference() => self.cell_reference(),
             c if c.is_numeric() => self.number(),
+            '-' if self.at_negative_number() => self.number(),
             _ => {
                 let token = Token::try_from(self.source[0]);
                 self.source = &self.source[1..];
@@ -228,10 +723,44 @@ impl<'a> Tokenizer<'a> {
             }
         };
 
+        self.expects_operand = !matches!(
+            token,
+            Ok(Token::Number(_)
+                | Token::Bool(_)
+                | Token::Str(_)
+                | Token::Name(_)
+                | Token::CellRef(_)
+                | Token::Label(_)
+                | Token::CellRange(_)
+                | Token::Percent
+                | Token::RightParen)
+        );
         Some(token)
     }
 }
 
+/// Whether `source` starts with digits directly followed by letters that are
+/// not a numeric suffix or an exponent, as in the legacy row-first reference
+/// `1A`. `1e3` and `1e-3` are numbers.
+fn starts_row_first_reference(source: &[char]) -> bool {
+    let digits = source.iter().take_while(|c| c.is_numeric()).count();
+    let letters = source[digits..]
+        .iter()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>();
+    if letters.eq_ignore_ascii_case("e") {
+        let exponent = &source[digits + 1..];
+        let exponent = match exponent.first() {
+            Some('+' | '-') => &exponent[1..],
+            _ => exponent,
+        };
+        if exponent.first().is_some_and(|c| c.is_ascii_digit()) {
+            return false;
+        }
+    }
+    !letters.is_empty() && !letters.eq_ignore_ascii_case("bp")
+}
+
 impl<'a> Iterator for Tokenizer<'a> {
     type Item = TableResult<Token>;
     fn next(&mut self) -> Option<Self::Item> {
@@ -239,10 +768,26 @@ impl<'a> Iterator for Tokenizer<'a> {
     }
 }
 
+/// Tokenizes a whole formula (without the leading `=`), stopping at the first error.
+pub fn tokenize(source: &str) -> TableResult<Vec<Token>> {
+    let chars = source.chars().collect::<Vec<_>>();
+    Tokenizer::new(&chars).collect()
+}
+
+/// A stable, space separated rendering of the token stream of `source`, one
+/// `Display`ed token per entry, for golden tests of the tokenizer.
+pub fn dump_tokens(source: &str) -> TableResult<String> {
+    let tokens = tokenize(source)?
+        .iter()
+        .map(Token::to_string)
+        .collect::<Vec<_>>();
+    Ok(tokens.join(" "))
+}
+
 #[cfg(test)]
 mod tests {
-    use bigdecimal::FromPrimitive;
-    use std::ops::Range;
+    use alloc::vec;
+    use core::ops::Range;
 
     use super::*;
 
@@ -252,7 +797,97 @@ mod tests {
         let tokens = tokenizer.collect::<Vec<TableResult<Token>>>();
         assert_eq!(tokens.len(), 1);
         let token = tokens[0].clone().unwrap();
-        assert_eq!(token, Token::Number(BigDecimal::from_f64(1.2).unwrap()))
+        assert_eq!(token, Token::Number(Number::from_str("1.2").unwrap()))
+    }
+
+    #[test]
+    fn test_parse_number_suffixes() {
+        for (input, expected) in [("50%", "0.5"), ("25bp", "0.0025"), ("12.5%", "0.125")] {
+            let input = input.chars().collect::<Vec<_>>();
+            let tokens = Tokenizer::new(&input).collect::<Vec<TableResult<Token>>>();
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(
+                tokens[0].clone().unwrap(),
+                Token::Number(Number::from_str(expected).unwrap())
+            );
+        }
+
+        let input = ['2', '5', 'x'];
+        match Tokenizer::new(&input).next() {
+            Some(Err(TableError::InvalidCell(msg))) => assert!(msg.contains("suffix: x")),
+            other => panic!("expected an unknown suffix error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_scientific_notation() {
+        for (input, expected) in [("1e3", "1000"), ("2.5E-2", "0.025"), ("4e+1", "40")] {
+            let input = input.chars().collect::<Vec<_>>();
+            let tokens = Tokenizer::new(&input).collect::<Vec<TableResult<Token>>>();
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(
+                tokens[0].clone().unwrap(),
+                Token::Number(Number::from_str(expected).unwrap())
+            );
+        }
+
+        for input in ["1e", "1e+", "2.5E-x"] {
+            let input = input.chars().collect::<Vec<_>>();
+            match Tokenizer::new(&input).next() {
+                Some(Err(TableError::InvalidCell(msg))) => assert!(msg.contains("exponent")),
+                other => panic!("expected a missing exponent error, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_negative_number_literals() {
+        let number = |n: i32| Token::Number(Number::from(n));
+        assert_eq!(tokenize("-5").unwrap(), vec![number(-5)]);
+        assert_eq!(
+            tokenize("a1-5").unwrap(),
+            vec![Token::CellRef((0, 0).into()), Token::Minus, number(5)]
+        );
+        assert_eq!(
+            tokenize("(-5)").unwrap(),
+            vec![Token::LeftParen, number(-5), Token::RightParen]
+        );
+        assert_eq!(
+            tokenize("2 - -5").unwrap(),
+            vec![number(2), Token::Minus, number(-5)]
+        );
+        assert_eq!(
+            tokenize("sum(1,-5)").unwrap(),
+            vec![
+                Token::Sum,
+                Token::LeftParen,
+                number(1),
+                Token::Comma,
+                number(-5),
+                Token::RightParen
+            ]
+        );
+        assert_eq!(
+            tokenize("(1)-5").unwrap(),
+            vec![
+                Token::LeftParen,
+                number(1),
+                Token::RightParen,
+                Token::Minus,
+                number(5)
+            ]
+        );
+        assert_eq!(
+            tokenize("-a1").unwrap(),
+            vec![Token::Minus, Token::CellRef((0, 0).into())]
+        );
+
+        let input = "-1A".chars().collect::<Vec<_>>();
+        let tokens = Tokenizer::new_lenient(&input).collect::<TableResult<Vec<_>>>();
+        assert_eq!(
+            tokens.unwrap(),
+            vec![Token::Minus, Token::CellRef((0, 0).into())]
+        );
     }
 
     #[test]
@@ -261,18 +896,145 @@ mod tests {
         let tokens = tokenizer.collect::<Vec<TableResult<Token>>>();
         assert_eq!(tokens.len(), 1);
         let token = tokens[0].clone().unwrap();
-        assert_eq!(token, Token::CellRef((26, 11)));
+        assert_eq!(token, Token::CellRef((11, 26).into()));
+    }
+
+    #[test]
+    fn test_oversized_column_reference() {
+        let tokens = tokenize("zzzzzzzzzzzzzzz1");
+        assert!(matches!(tokens, Err(TableError::InvalidCell(_))));
+    }
+
+    #[test]
+    fn test_absolute_cell_reference() {
+        let cell = |row_absolute, col_absolute| {
+            Token::CellRef(CellRef {
+                row: 0,
+                col: 1,
+                row_absolute,
+                col_absolute,
+            })
+        };
+        for (source, expected) in [
+            ("b1", cell(false, false)),
+            ("$b$1", cell(true, true)),
+            ("$b1", cell(false, true)),
+            ("b$1", cell(true, false)),
+            ("$B$1", cell(true, true)),
+        ] {
+            assert_eq!(
+                tokenize(source).unwrap(),
+                vec![expected.clone()],
+                "{source}"
+            );
+            assert_eq!(expected.to_string(), source.to_ascii_lowercase());
+        }
+
+        let range = CellRange {
+            start: CellRef {
+                row_absolute: true,
+                col_absolute: true,
+                ..(0, 0).into()
+            },
+            end: CellRef {
+                row_absolute: true,
+                ..(1, 1).into()
+            },
+        };
+        assert_eq!(tokenize("$a$1:b$2").unwrap(), vec![Token::CellRange(range)]);
+        assert_eq!(range.to_string(), "$a$1:b$2");
+        assert_eq!((range.rows(), range.cols()), (0..2, 0..2));
+        assert_eq!(tokenize("b$2:$a$1").unwrap(), vec![Token::CellRange(range)]);
+        assert_eq!(tokenize("b$1:$a2").unwrap()[0].to_string(), "$a$1:b2");
+        assert_eq!(
+            tokenize("b$1-$a1").unwrap(),
+            vec![
+                cell(true, false),
+                Token::Minus,
+                Token::CellRef(CellRef {
+                    col_absolute: true,
+                    ..(0, 0).into()
+                })
+            ]
+        );
+        for source in ["$", "$1", "a$", "$$a1"] {
+            assert!(tokenize(source).is_err(), "{source}");
+        }
+    }
+
+    #[test]
+    fn test_cell_name_round_trip() {
+        for (name, coord) in [
+            ("a1", (0, 0)),
+            ("z3", (2, 25)),
+            ("aa12", (11, 26)),
+            ("ba7", (6, 52)),
+        ] {
+            assert_eq!(cell_name(coord.0, coord.1), name);
+            let input = name.chars().collect::<Vec<_>>();
+            let token = Tokenizer::new(&input).next().unwrap().unwrap();
+            assert_eq!(token, Token::CellRef(coord.into()));
+        }
+    }
+
+    #[test]
+    fn test_lenient_row_first_reference() {
+        use Token::*;
+        let tokenize = |input: &str| {
+            let input = input.chars().collect::<Vec<_>>();
+            Tokenizer::new_lenient(&input)
+                .collect::<TableResult<Vec<Token>>>()
+                .unwrap()
+        };
+        assert_eq!(tokenize("1A"), vec![CellRef((0, 0).into())]);
+        assert_eq!(tokenize("12ab"), tokenize("ab12"));
+        assert_eq!(tokenize("1a:2b"), tokenize("a1:b2"));
+        assert_eq!(
+            tokenize("1"),
+            vec![Token::Number(crate::number::Number::from(1))]
+        );
+        assert_eq!(
+            tokenize("1a + 2.5"),
+            vec![
+                CellRef((0, 0).into()),
+                Plus,
+                Token::Number(crate::number::Number::from_str("2.5").unwrap())
+            ]
+        );
+        assert_eq!(
+            tokenize("25bp"),
+            vec![Token::Number(
+                crate::number::Number::from_str("0.0025").unwrap()
+            )]
+        );
+        for (input, expected) in [("1e3", "1000"), ("2E-2", "0.02"), ("-1e+1", "-10")] {
+            assert_eq!(
+                tokenize(input),
+                vec![Token::Number(
+                    crate::number::Number::from_str(expected).unwrap()
+                )],
+                "{input}"
+            );
+        }
+        assert_eq!(tokenize("1e"), vec![CellRef((0, 4).into())]);
+
+        let input = ['1', 'a'];
+        assert!(Tokenizer::new(&input).next().unwrap().is_err());
     }
 
     #[test]
     fn test_parse_cell_op() {
         use Token::*;
-        for (op, expected_op_token) in vec![('+', Plus), ('-', Minus), ('/', Slash), ('*', Star)] {
+        for (op, expected_op_token) in [('+', Plus), ('-', Minus), ('/', Slash), ('*', Star)] {
             let input = &[' ', ' ', 'a', '1', ' ', op, ' ', 'b', '3'];
             let tokenizer = Tokenizer::new(input);
             let tokens = tokenizer.collect::<Vec<TableResult<Token>>>();
             assert_eq!(tokens.len(), 3);
-            let expected_tokens = vec![CellRef((0, 0)), expected_op_token, CellRef((1, 2))];
+            let expected_tokens = vec![
+                CellRef((0, 0).into()),
+                expected_op_token,
+                CellRef((2, 1).into()),
+            ];
             for (token, expected_token) in tokens.iter().zip(expected_tokens) {
                 assert!(token.is_ok());
                 assert_eq!(token.clone().unwrap(), expected_token);
@@ -289,10 +1051,38 @@ mod tests {
         assert_eq!(tokens.len(), 1);
         assert_eq!(
             tokens[0].clone().unwrap(),
-            CellRange((Range { start: 0, end: 1 }, Range { start: 0, end: 5 }))
+            CellRange((Range { start: 0, end: 5 }, Range { start: 0, end: 1 }).into())
         )
     }
 
+    #[test]
+    fn test_parse_block_range() {
+        use Token::*;
+        for input in ["a1:c3", "c3:a1", "a3:c1"] {
+            let input = input.chars().collect::<Vec<_>>();
+            let tokens = Tokenizer::new(&input).collect::<Vec<TableResult<Token>>>();
+            assert_eq!(tokens.len(), 1);
+            assert_eq!(
+                tokens[0].clone().unwrap(),
+                CellRange((Range { start: 0, end: 3 }, Range { start: 0, end: 3 }).into())
+            );
+        }
+    }
+
+    #[test]
+    fn test_range_endpoint_must_be_reference() {
+        for input in ["a1:3", "a1:a(1+2)", "a1:"] {
+            let input = input.chars().collect::<Vec<_>>();
+            let mut tokenizer = Tokenizer::new(&input);
+            match tokenizer.next() {
+                Some(Err(TableError::InvalidCell(msg))) => {
+                    assert!(msg.starts_with("Invalid cell range"))
+                }
+                other => panic!("expected invalid cell range, got {other:?}"),
+            }
+        }
+    }
+
     #[test]
     fn test_sum_cell_range() {
         use Token::*;
@@ -305,10 +1095,10 @@ mod tests {
         let expected_tokens = vec![
             Sum,
             LeftParen,
-            CellRange((Range { start: 0, end: 2 }, Range { start: 0, end: 22 })),
+            CellRange((Range { start: 0, end: 22 }, Range { start: 0, end: 2 }).into()),
             RightParen,
             Plus,
-            CellRef((2, 2)),
+            CellRef((2, 2).into()),
         ];
         for (token, expected_token) in tokens.iter().zip(expected_tokens) {
             assert!(token.is_ok());
@@ -328,16 +1118,122 @@ mod tests {
         let expected_tokens = vec![
             Sum,
             LeftParen,
-            CellRef((0, 0)),
+            CellRef((0, 0).into()),
             Comma,
-            CellRef((1, 21)),
+            CellRef((21, 1).into()),
             RightParen,
             Plus,
-            CellRef((2, 2)),
+            CellRef((2, 2).into()),
         ];
         for (token, expected_token) in tokens.iter().zip(expected_tokens) {
             assert!(token.is_ok());
             assert_eq!(token.clone().unwrap(), expected_token)
         }
     }
+
+    #[test]
+    fn test_golden_token_stream() {
+        assert_eq!(
+            dump_tokens("sum(a1:b5) + avg(c1, d2) * -3").unwrap(),
+            "sum ( a1:b5 ) + mean ( c1 , d2 ) * -3"
+        );
+        assert!(dump_tokens("a1 # 2").is_err());
+    }
+
+    #[test]
+    fn test_range_whitespace_around_colon() {
+        let expected = tokenize("a1:b5").unwrap();
+        for source in ["a1 : b5", "a1 :b5", "a1:  b5", " a1\t:\tb5 "] {
+            assert_eq!(tokenize(source).unwrap(), expected, "{source}");
+        }
+        assert_eq!(
+            tokenize("sum(a1 : b5) + c1").unwrap(),
+            tokenize("sum(a1:b5) + c1").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_names() {
+        assert_eq!(
+            tokenize("Price * unit_cost2 + a1").unwrap(),
+            vec![
+                Token::Name("price".to_string()),
+                Token::Star,
+                Token::Name("unit_cost2".to_string()),
+                Token::Plus,
+                Token::CellRef((0, 0).into()),
+            ]
+        );
+        assert_eq!(
+            tokenize("summary").unwrap(),
+            vec![Token::Name("summary".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_string_literals() {
+        assert_eq!(
+            tokenize(r##"text(a1, "#,##0.00")"##).unwrap(),
+            vec![
+                Token::Text,
+                Token::LeftParen,
+                Token::CellRef((0, 0).into()),
+                Token::Comma,
+                Token::Str("#,##0.00".to_string()),
+                Token::RightParen,
+            ]
+        );
+        let quoted = tokenize(r#""say ""hi""""#).unwrap();
+        assert_eq!(quoted, vec![Token::Str(r#"say "hi""#.to_string())]);
+        assert_eq!(quoted[0].to_string(), r#""say ""hi""""#);
+        assert!(tokenize(r#""open"#).is_err());
+    }
+
+    #[test]
+    fn test_sheet_relative_reference() {
+        assert_eq!(tokenize(".A1").unwrap(), tokenize("a1").unwrap());
+        assert_eq!(tokenize(".a1:.B2").unwrap(), tokenize("a1:b2").unwrap());
+        assert!(tokenize(".5").is_err());
+    }
+
+    #[test]
+    fn test_non_finite_words() {
+        for source in ["inf", "INF", "Infinity", "-inf + 1"] {
+            match tokenize(source) {
+                Err(TableError::InvalidCell(msg)) => {
+                    assert_eq!(msg, "infinity is not representable")
+                }
+                other => panic!("expected an infinity error for {source}, got {other:?}"),
+            }
+        }
+        assert!(matches!(
+            tokenize("nan"),
+            Err(TableError::InvalidCell(msg)) if msg == "NaN is not representable"
+        ));
+        assert!(tokenize("inf1").unwrap()[0].is_cell_ref());
+    }
+
+    #[test]
+    fn test_floor_division() {
+        assert_eq!(dump_tokens("7 // 2").unwrap(), "7 // 2");
+        assert_eq!(dump_tokens("7 / / 2").unwrap(), "7 / / 2");
+        assert_eq!(
+            tokenize("a1//b1").unwrap(),
+            vec![
+                Token::CellRef((0, 0).into()),
+                Token::SlashSlash,
+                Token::CellRef((0, 1).into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(dump_tokens("@Total * 2").unwrap(), "@total * 2");
+        assert_eq!(
+            tokenize("@tax_rate").unwrap(),
+            vec![Token::Label("tax_rate".to_string())]
+        );
+        assert!(tokenize("@ total").is_err());
+    }
 }
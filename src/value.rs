@@ -0,0 +1,69 @@
+use super::error::{TableError, TableResult};
+use crate::number::Number;
+use alloc::{format, string::String};
+
+/// The typed result of evaluating a cell or expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Number(Number),
+    Text(String),
+    Bool(bool),
+    /// No value, as held by an empty cell.
+    Empty,
+}
+
+impl Value {
+    /// The value as a number for arithmetic. Booleans count as 1 and 0;
+    /// text is a runtime error and `Empty` an `EmptyCellEvaluation`.
+    pub fn as_number(&self) -> TableResult<Number> {
+        match self {
+            Value::Number(d) => Ok(d.clone()),
+            Value::Bool(b) => Ok(Number::from(*b as u8)),
+            Value::Text(s) => Err(TableError::runtime_error(format!(
+                "Expected a number, got text \"{s}\""
+            ))),
+            Value::Empty => Err(TableError::EmptyCellEvaluation),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(d: Number) -> Self {
+        Value::Number(d)
+    }
+}
+
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Value::Number(d) => write!(f, "{d}"),
+            Value::Text(s) => write!(f, "{s}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Empty => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_number() {
+        assert_eq!(
+            Value::Number(Number::from(3)).as_number().unwrap(),
+            Number::from(3)
+        );
+        assert_eq!(Value::Bool(true).as_number().unwrap(), Number::from(1));
+        assert_eq!(Value::Bool(false).as_number().unwrap(), Number::from(0));
+        assert!(matches!(
+            Value::Text("abc".into()).as_number(),
+            Err(TableError::RuntimeError(_))
+        ));
+        assert!(matches!(
+            Value::Empty.as_number(),
+            Err(TableError::EmptyCellEvaluation)
+        ));
+        assert_eq!(alloc::format!("{}", Value::Empty), "");
+    }
+}